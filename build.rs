@@ -12,7 +12,8 @@ mod inner {
   pub fn main() {
     let out_dir = env::var_os("OUT_DIR").unwrap();
 
-    let files = vec!["event/request.rs", "event/response.rs"];
+    let files = vec!["event/request.rs", "event/response.rs", "definitions/listing.rs",
+                     "format/json.rs", "format/messages.rs"];
 
     files.into_iter().foreach(|file| {
       let src_string = "src/".to_owned() + file +".in";
@@ -22,7 +23,7 @@ mod inner {
       println!("dst {:?}", dst);
 
       // Don't care if directory already exists
-      let _ = fs::create_dir(Path::new(&out_dir).join("event"));
+      let _ = fs::create_dir_all(dst.parent().unwrap());
 
       serde_codegen::expand(&src, &dst).unwrap();
     })
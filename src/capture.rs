@@ -0,0 +1,184 @@
+//! Capture of what steps print to stdout
+//!
+//! Output is captured at the file descriptor level, so it includes anything
+//! written to stdout by the process while a capture is running, not only
+//! what Rust's `print!` writes. Capturing is only supported on unix, elsewhere
+//! output passes through and nothing is captured.
+//!
+//! As stdout belongs to the whole process, so does a capture: it takes in what
+//! every thread writes while it runs, and captures wait for each other.
+
+use libc;
+
+/// The file descriptor of stdout, which is what steps' output is captured
+/// from outside of tests
+pub const STDOUT: libc::c_int = 1;
+
+#[cfg(unix)]
+pub use self::unix::Capture;
+
+#[cfg(not(unix))]
+pub use self::fallback::Capture;
+
+#[cfg(unix)]
+mod unix {
+  use std::fs::File;
+  use std::io::{self, Read, Seek, SeekFrom, Write};
+  use std::os::unix::io::{AsRawFd, FromRawFd};
+  use std::sync::{Mutex, MutexGuard};
+
+  use libc;
+
+  lazy_static! {
+    // Held by the running capture, as stdout can only be redirected by one at
+    // a time
+    static ref CAPTURING: Mutex<()> = Mutex::new(());
+  }
+
+  // Makes sure anything buffered so far ends up where stdout currently points
+  fn flush_stdout() {
+    let _ = io::stdout().flush();
+  }
+
+  /// A capture of a file descriptor, usually stdout, into a temporary file,
+  /// running until it is finished
+  ///
+  /// Only one capture runs at a time, so starting one waits for any other to
+  /// finish. Dropping a capture without finishing it restores the file
+  /// descriptor.
+  pub struct Capture {
+    fd: libc::c_int,
+    saved: libc::c_int,
+    file: File,
+    // NOTE: Only released once the capture is dropped, after restoring stdout
+    _running: MutexGuard<'static, ()>,
+  }
+
+  impl Capture {
+    pub fn start(fd: libc::c_int) -> io::Result<Capture> {
+      // NOTE: A capture that panicked has still restored stdout when dropped
+      let running = CAPTURING.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+      Capture::redirect(fd, running)
+    }
+
+    fn redirect(fd: libc::c_int, running: MutexGuard<'static, ()>) -> io::Result<Capture> {
+      flush_stdout();
+
+      unsafe {
+        let tmp = libc::tmpfile();
+        if tmp.is_null() {
+          return Err(io::Error::last_os_error());
+        }
+        let tmp_fd = libc::dup(libc::fileno(tmp));
+        libc::fclose(tmp);
+        if tmp_fd < 0 {
+          return Err(io::Error::last_os_error());
+        }
+        let file = File::from_raw_fd(tmp_fd);
+
+        let saved = libc::dup(fd);
+        if saved < 0 {
+          return Err(io::Error::last_os_error());
+        }
+        if libc::dup2(file.as_raw_fd(), fd) < 0 {
+          let err = io::Error::last_os_error();
+          libc::close(saved);
+          return Err(err);
+        }
+
+        Ok(Capture {
+          fd: fd,
+          saved: saved,
+          file: file,
+          _running: running,
+        })
+      }
+    }
+
+    /// Restore the file descriptor, yielding everything written to it during
+    /// the capture, with anything that is not UTF-8 replaced
+    pub fn finish(mut self) -> io::Result<String> {
+      self.restore();
+
+      let mut output = Vec::new();
+      try!(self.file.seek(SeekFrom::Start(0)));
+      try!(self.file.read_to_end(&mut output));
+      Ok(String::from_utf8_lossy(&output).into_owned())
+    }
+
+    fn restore(&mut self) {
+      if self.saved < 0 {
+        return;
+      }
+      flush_stdout();
+
+      unsafe {
+        libc::dup2(self.saved, self.fd);
+        libc::close(self.saved);
+      }
+      self.saved = -1;
+    }
+  }
+
+  impl Drop for Capture {
+    fn drop(&mut self) {
+      self.restore();
+    }
+  }
+}
+
+#[cfg(not(unix))]
+mod fallback {
+  use std::io;
+
+  use libc;
+
+  pub struct Capture;
+
+  impl Capture {
+    pub fn start(_fd: libc::c_int) -> io::Result<Capture> {
+      Ok(Capture)
+    }
+
+    pub fn finish(self) -> io::Result<String> {
+      Ok(String::new())
+    }
+  }
+}
+
+#[cfg(all(test, unix))]
+mod test {
+  use super::*;
+
+  use std::fs::File;
+  use std::io::{Read, Write};
+  use std::os::unix::io::AsRawFd;
+
+  use tempdir::TempDir;
+
+  #[test]
+  fn it_captures_what_is_written_to_a_file_descriptor() {
+    let dir = TempDir::new("cucumber-capture").unwrap();
+    let path = dir.path().join("out.txt");
+    let mut file = File::create(&path).unwrap();
+
+    let capture = Capture::start(file.as_raw_fd()).unwrap();
+    file.write_all(b"Hello from a step\n").unwrap();
+    assert_eq!(capture.finish().unwrap(), "Hello from a step\n");
+    file.write_all(b"Goodbye\n").unwrap();
+
+    let mut written = String::new();
+    File::open(&path).and_then(|mut file| file.read_to_string(&mut written)).unwrap();
+    assert_eq!(written, "Goodbye\n");
+  }
+
+  #[test]
+  fn it_keeps_output_that_is_not_utf8() {
+    let dir = TempDir::new("cucumber-capture").unwrap();
+    let mut file = File::create(dir.path().join("out.txt")).unwrap();
+
+    let capture = Capture::start(file.as_raw_fd()).unwrap();
+    file.write_all(b"Hello \xff from a step\n").unwrap();
+    assert_eq!(capture.finish().unwrap(), "Hello \u{fffd} from a step\n");
+  }
+}
@@ -0,0 +1,173 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+
+use serde_json;
+
+use event::request::{BeginScenarioRequest, EndScenarioRequest, InvokeArgument, InvokeRequest,
+                     Request, SnippetTextRequest, StepMatchesRequest};
+use event::response::{InvokeResponse, Response};
+
+/// A Cucumber Wire Protocol client
+///
+/// Connects to any wire server, such as a running
+/// [Server](../server/struct.Server.html), and exchanges typed
+/// [Requests](../event/request/enum.Request.html) and
+/// [Responses](../event/response/enum.Response.html) with it. This is mostly
+/// useful for testing step servers without a Ruby Cucumber installation.
+///
+/// # Example
+///
+/// ```no_run
+/// use cucumber::{Server, WireClient, WorldRunner};
+///
+/// fn main() {
+///   let world: u32 = 0;
+///   let server = Server::new(WorldRunner::new(world));
+///   let (handle, stop_tx) = server.start(Some("127.0.0.1:7878"));
+///
+///   let mut client = WireClient::connect("127.0.0.1:7878").unwrap();
+///   client.begin_scenario(Vec::new()).unwrap();
+///   let matches = client.step_matches("I have 5 coins").unwrap();
+///   client.end_scenario(Vec::new()).unwrap();
+///
+///   stop_tx.send(()).unwrap();
+///   handle.join().unwrap();
+/// }
+/// ```
+pub struct WireClient {
+  stream: TcpStream,
+  reader: BufReader<TcpStream>,
+}
+
+impl WireClient {
+  /// Connect to a wire server at the given address
+  pub fn connect<A: ToSocketAddrs>(addr: A) -> io::Result<WireClient> {
+    let stream = try!(TcpStream::connect(addr));
+    let reader = BufReader::new(try!(stream.try_clone()));
+
+    Ok(WireClient {
+      stream: stream,
+      reader: reader,
+    })
+  }
+
+  /// Send a raw request, and wait for the server's response
+  ///
+  /// Responses are interpreted in light of the request, so `begin_scenario`
+  /// and `end_scenario` yield `Response::BeginScenario` and
+  /// `Response::EndScenario`, and a failure to anything other than `invoke`
+  /// yields `Response::Fail`.
+  pub fn send(&mut self, req: Request) -> io::Result<Response> {
+    let line = format!("{}\n", serde_json::to_string(&req).unwrap());
+    try!(self.stream.write_all(line.as_bytes()));
+
+    let mut body = String::new();
+    if try!(self.reader.read_line(&mut body)) == 0 {
+      return Err(io::Error::new(ErrorKind::UnexpectedEof, "Wire server closed the connection"));
+    }
+
+    let response = try!(serde_json::from_str::<Response>(&body).map_err(|err| {
+      io::Error::new(ErrorKind::InvalidData,
+                     format!("Malformed response [{}]: {}", body.trim_right(), err))
+    }));
+
+    Ok(match (req, response) {
+      (Request::BeginScenario(_), Response::Invoke(InvokeResponse::Success)) => {
+        Response::BeginScenario
+      },
+      (Request::EndScenario(_), Response::Invoke(InvokeResponse::Success)) => Response::EndScenario,
+      (Request::Invoke(_), response) => response,
+      (_, Response::Invoke(InvokeResponse::Fail(message))) => Response::Fail(message),
+      (_, response) => response,
+    })
+  }
+
+  /// Ask the server for step definitions matching a step
+  pub fn step_matches(&mut self, name_to_match: &str) -> io::Result<Response> {
+    self.send(Request::StepMatches(StepMatchesRequest { name_to_match: name_to_match.to_owned() }))
+  }
+
+  /// Invoke a step definition by the id the server gave in a step match
+  pub fn invoke(&mut self, id: &str, args: Vec<InvokeArgument>) -> io::Result<Response> {
+    self.send(Request::Invoke(InvokeRequest {
+      id: id.to_owned(),
+      args: args,
+    }))
+  }
+
+  /// Signal the start of a scenario with the given tags
+  pub fn begin_scenario(&mut self, tags: Vec<String>) -> io::Result<Response> {
+    self.send(Request::BeginScenario(BeginScenarioRequest { tags: tags }))
+  }
+
+  /// Signal the end of a scenario with the given tags
+  pub fn end_scenario(&mut self, tags: Vec<String>) -> io::Result<Response> {
+    self.send(Request::EndScenario(EndScenarioRequest { tags: tags }))
+  }
+
+  /// Ask the server for a snippet implementing an undefined step
+  pub fn snippet_text(&mut self,
+                      step_keyword: &str,
+                      step_name: &str,
+                      multiline_arg_class: &str)
+                      -> io::Result<Response> {
+    self.send(Request::SnippetText(SnippetTextRequest {
+      step_keyword: step_keyword.to_owned(),
+      multiline_arg_class: multiline_arg_class.to_owned(),
+      step_name: step_name.to_owned(),
+    }))
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use server::Server;
+  use event::request::{InvokeArgument, Request};
+  use event::response::{FailMessage, InvokeResponse, Response, Step, StepMatchesResponse};
+
+  #[test]
+  fn it_talks_to_a_server() {
+    let server = Server::new(|req| {
+      match req {
+        Request::BeginScenario(_) => Response::BeginScenario,
+        Request::Invoke(ref params) if params.args.len() == 0 => {
+          Response::Invoke(InvokeResponse::Success)
+        },
+        Request::Invoke(_) => Response::Invoke(InvokeResponse::pending_from_str("later")),
+        Request::StepMatches(_) => {
+          Response::StepMatches(StepMatchesResponse::Match(vec![Step {
+                                                                  id: "1".to_owned(),
+                                                                  args: Vec::new(),
+                                                                  source: "test".to_owned(),
+                                                                }]))
+        },
+        Request::EndScenario(_) => Response::Fail(FailMessage::new("no scenario".to_owned())),
+        Request::SnippetText(_) => Response::SnippetText("Snippet".to_owned()),
+      }
+    });
+    let (handle, stop_tx) = server.start(Some("127.0.0.1:1237"));
+    let mut client = WireClient::connect("127.0.0.1:1237").unwrap();
+
+    assert_eq!(client.begin_scenario(vec!["@tag".to_owned()]).unwrap(),
+               Response::BeginScenario);
+    assert_eq!(client.step_matches("test").unwrap(),
+               Response::StepMatches(StepMatchesResponse::Match(vec![Step {
+                                                                       id: "1".to_owned(),
+                                                                       args: Vec::new(),
+                                                                       source: "test".to_owned(),
+                                                                     }])));
+    assert_eq!(client.invoke("1", Vec::new()).unwrap(),
+               Response::Invoke(InvokeResponse::Success));
+    assert_eq!(client.invoke("1", vec![InvokeArgument::from_str("arg")]).unwrap(),
+               Response::Invoke(InvokeResponse::pending_from_str("later")));
+    assert_eq!(client.snippet_text("Given", "test", "").unwrap(),
+               Response::SnippetText("Snippet".to_owned()));
+    assert_eq!(client.end_scenario(Vec::new()).unwrap(),
+               Response::Fail(FailMessage::new("no scenario".to_owned())));
+
+    stop_tx.send(()).unwrap();
+    handle.join().unwrap();
+  }
+}
@@ -1,10 +1,91 @@
-use regex::Regex;
+use regex::{self, Regex};
 
 #[allow(dead_code)]
 pub fn build(str: &str) -> Regex {
   Regex::new(str).unwrap()
 }
 
+/// Translate a Cucumber Expression into the equivalent regular expression
+///
+/// Supports the `{int}`, `{float}`, `{string}`, `{word}` and anonymous `{}`
+/// parameter types, optional text in parentheses, alternative words separated
+/// by `/`, and `\` to escape any of these. The result can be used wherever a
+/// regular expression is expected, including the step macros.
+///
+/// # Example
+///
+/// ```
+/// use cucumber::cucumber_regex;
+///
+/// fn main() {
+///   let regex = cucumber_regex::build(&cucumber_regex::expression("I have {int} cucumber(s)"));
+///   assert!(regex.is_match("I have 42 cucumbers"));
+///   assert!(regex.is_match("I have 1 cucumber"));
+/// }
+/// ```
+pub fn expression(expr: &str) -> String {
+  let mut result = "^".to_owned();
+  let mut alternatives: Vec<String> = Vec::new();
+  let mut current = String::new();
+  let mut chars = expr.chars();
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        if let Some(escaped) = chars.next() {
+          current.push(escaped);
+        }
+      },
+      '/' => {
+        alternatives.push(current.clone());
+        current.clear();
+      },
+      '{' => {
+        push_word(&mut result, &mut alternatives, &mut current);
+        let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+        result.push_str(parameter_regex(&name));
+      },
+      '(' => {
+        push_word(&mut result, &mut alternatives, &mut current);
+        let optional: String = chars.by_ref().take_while(|&c| c != ')').collect();
+        result.push_str(&format!("(?:{})?", regex::quote(&optional)));
+      },
+      c if c.is_whitespace() => {
+        push_word(&mut result, &mut alternatives, &mut current);
+        result.push(c);
+      },
+      c => current.push(c),
+    }
+  }
+
+  push_word(&mut result, &mut alternatives, &mut current);
+  result.push('$');
+  result
+}
+
+fn push_word(result: &mut String, alternatives: &mut Vec<String>, current: &mut String) {
+  if alternatives.is_empty() {
+    result.push_str(&regex::quote(current));
+  } else {
+    alternatives.push(current.clone());
+    let quoted: Vec<String> = alternatives.iter().map(|alt| regex::quote(alt)).collect();
+    result.push_str(&format!("(?:{})", quoted.join("|")));
+    alternatives.clear();
+  }
+  current.clear();
+}
+
+fn parameter_regex(name: &str) -> &'static str {
+  match name {
+    "int" => "(-?\\d+)",
+    "float" => "(-?\\d*\\.?\\d+)",
+    "string" => "\"([^\"]*)\"",
+    "word" => "([^\\s]+)",
+    "" => "(.*)",
+    _ => panic!("Unknown parameter type {{{}}} in Cucumber Expression", name),
+  }
+}
+
 #[cfg(test)]
 mod test {
   use super::*;
@@ -14,4 +95,29 @@ mod test {
     let regex = build("^Hello Regex$");
     assert!(regex.is_match("Hello Regex"));
   }
+
+  #[test]
+  fn it_translates_expression_parameters() {
+    let regex = build(&expression("I have {int} {word} worth {float} called {string}"));
+    let captures = regex.captures("I have -3 apples worth 1.5 called \"my apples\"").unwrap();
+    assert_eq!(captures.at(1), Some("-3"));
+    assert_eq!(captures.at(2), Some("apples"));
+    assert_eq!(captures.at(3), Some("1.5"));
+    assert_eq!(captures.at(4), Some("my apples"));
+  }
+
+  #[test]
+  fn it_translates_optional_and_alternative_text() {
+    let regex = build(&expression("I have {int} cucumber(s) in my belly/stomach"));
+    assert!(regex.is_match("I have 1 cucumber in my belly"));
+    assert!(regex.is_match("I have 2 cucumbers in my stomach"));
+    assert!(!regex.is_match("I have 2 cucumbers in my head"));
+  }
+
+  #[test]
+  fn it_escapes_expression_text() {
+    let regex = build(&expression("a \\{int\\} costs $5.00 \\(or less\\) and/or more"));
+    assert!(regex.is_match("a {int} costs $5.00 (or less) and more"));
+    assert!(!regex.is_match("a 5 costs $5.00 (or less) and more"));
+  }
 }
@@ -0,0 +1,106 @@
+//! Printable listings of the step definitions registered with a
+//! [Cucumber](../../state/struct.Cucumber.html)
+
+#[cfg(feature = "serde_macros")]
+include!("listing.rs.in");
+
+#[cfg(not(feature = "serde_macros"))]
+include!(concat!(env!("OUT_DIR"), "/definitions/listing.rs"));
+
+use serde_json;
+
+use definitions::registration::StepKeyword;
+use state::StepId;
+
+/// The formats step definitions can be listed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ListFormat {
+  /// One definition per line, as `Given /pattern/ # file:line (arity)`
+  Text,
+  /// A JSON array of definitions
+  Json,
+}
+
+impl ListFormat {
+  /// Parse the value of a `--list-steps` command line flag, which may be
+  /// `--list-steps`, `--list-steps=text` or `--list-steps=json`
+  pub fn from_arg(arg: &str) -> Option<ListFormat> {
+    match arg {
+      "--list-steps" | "--list-steps=text" => Some(ListFormat::Text),
+      "--list-steps=json" => Some(ListFormat::Json),
+      _ => None,
+    }
+  }
+}
+
+/// Render step definitions in the given format
+pub fn list_steps<I>(format: ListFormat, definitions: I) -> String
+  where I: IntoIterator<Item = StepDefinition>
+{
+  match format {
+    ListFormat::Text => {
+      definitions.into_iter()
+        .map(|def| {
+          format!("{} /{}/ # {} ({} {})\n",
+                  def.keyword.map(|k| k.as_str()).unwrap_or("*"),
+                  def.pattern,
+                  def.source,
+                  def.arity,
+                  if def.arity == 1 { "argument" } else { "arguments" })
+        })
+        .collect::<Vec<_>>()
+        .concat()
+    },
+    ListFormat::Json => {
+      let definitions: Vec<StepDefinition> = definitions.into_iter().collect();
+      format!("{}\n", serde_json::to_string(&definitions).unwrap())
+    },
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use definitions::registration::StepKeyword;
+
+  fn definitions() -> Vec<StepDefinition> {
+    vec![StepDefinition {
+           id: 0,
+           keyword: Some(StepKeyword::Given),
+           pattern: "^I have (\\d+) coins$".to_owned(),
+           source: "src/steps.rs:10".to_owned(),
+           arity: 1,
+         },
+         StepDefinition {
+           id: 1,
+           keyword: None,
+           pattern: "^nothing$".to_owned(),
+           source: "src/steps.rs:20".to_owned(),
+           arity: 0,
+         }]
+  }
+
+  #[test]
+  fn it_parses_list_flags() {
+    assert_eq!(ListFormat::from_arg("--list-steps"), Some(ListFormat::Text));
+    assert_eq!(ListFormat::from_arg("--list-steps=json"), Some(ListFormat::Json));
+    assert_eq!(ListFormat::from_arg("--list-steps=yaml"), None);
+    assert_eq!(ListFormat::from_arg("--format"), None);
+  }
+
+  #[test]
+  fn it_lists_steps_as_text() {
+    assert_eq!(list_steps(ListFormat::Text, definitions()),
+               "Given /^I have (\\d+) coins$/ # src/steps.rs:10 (1 argument)\n* /^nothing$/ # \
+                src/steps.rs:20 (0 arguments)\n");
+  }
+
+  #[test]
+  fn it_lists_steps_as_json() {
+    assert_eq!(list_steps(ListFormat::Json, definitions()),
+               "[{\"id\":0,\"keyword\":\"Given\",\"pattern\":\"^I have (\\\\d+) coins$\",\
+                \"source\":\"src/steps.rs:10\",\"arity\":1},{\"id\":1,\"keyword\":null,\
+                \"pattern\":\"^nothing$\",\"source\":\"src/steps.rs:20\",\"arity\":0}]\n");
+  }
+}
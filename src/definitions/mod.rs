@@ -1,2 +1,4 @@
 pub mod registration;
 pub mod destructuring;
+pub mod snippet;
+pub mod listing;
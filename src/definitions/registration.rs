@@ -3,9 +3,80 @@
 use state::Cucumber;
 use regex::Regex;
 use event::request::InvokeArgument;
+use tags::TagExpression;
+use serde::{Serialize, Serializer};
+
+use std::fmt;
+use std::time::Duration;
 
 /// A "simpler" api-level step. Panic to fail.
-pub type SimpleStep<World> = Box<Fn(&Cucumber<World>, &mut World, Vec<InvokeArgument>) + Send>;
+///
+/// Steps are `Sync` as parallel native runs share them between threads.
+pub type SimpleStep<World> = Box<Fn(&Cucumber<World>, &mut World, Vec<InvokeArgument>) +
+                                 Send + Sync>;
+
+/// A hook run around scenarios. Panic to fail.
+pub type Hook<World> = Box<Fn(&Cucumber<World>, &mut World) + Send + Sync>;
+
+/// A hook run once a whole run is over, without a World. Panic to fail.
+pub type RunHook<World> = Box<Fn(&Cucumber<World>) + Send + Sync>;
+
+// NOTE: Closures only take a reference of any lifetime when passed straight
+// to a function that asks for one, which the AfterAll macro goes through
+#[doc(hidden)]
+pub fn run_hook<World, F>(hook: F) -> RunHook<World>
+  where F: Fn(&Cucumber<World>) + Send + Sync + 'static
+{
+  Box::new(hook)
+}
+
+/// When a hook runs
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum HookKind {
+  /// Before the steps of every scenario
+  Before,
+  /// After the steps of every scenario, whether they passed or not
+  After,
+}
+
+impl HookKind {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      &HookKind::Before => "Before",
+      &HookKind::After => "After",
+    }
+  }
+}
+
+/// The keyword a step was registered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StepKeyword {
+  Given,
+  When,
+  Then,
+}
+
+impl StepKeyword {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      &StepKeyword::Given => "Given",
+      &StepKeyword::When => "When",
+      &StepKeyword::Then => "Then",
+    }
+  }
+}
+
+impl fmt::Display for StepKeyword {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    f.write_str(self.as_str())
+  }
+}
+
+impl Serialize for StepKeyword {
+  fn serialize<S: Serializer>(&self, s: &mut S) -> Result<(), S::Error> {
+    s.serialize_str(self.as_str())
+  }
+}
 
 /// An interface for registering steps
 ///
@@ -18,19 +89,108 @@ pub trait CucumberRegistrar<World> {
   fn given(&mut self, file: &str, line: u32, Regex, SimpleStep<World>);
   fn when(&mut self, file: &str, line: u32, Regex, SimpleStep<World>);
   fn then(&mut self, file: &str, line: u32, Regex, SimpleStep<World>);
+  /// Limit how long the step registered with a regular expression may run
+  /// before it is failed
+  ///
+  /// Registrars that cannot time steps out ignore this, which is what it does
+  /// by default.
+  #[allow(unused_variables)]
+  fn timeout(&mut self, regex: &str, timeout: Duration) {}
+  /// Mark the step registered with a regular expression as one that only
+  /// checks its arguments in a dry run, as the steps of the step macros do
+  ///
+  /// Dry runs do not invoke the other steps. Registrars without dry runs
+  /// ignore this, which is what it does by default.
+  #[allow(unused_variables)]
+  fn checks_arguments(&mut self, regex: &str) {}
+  /// Add a hook run before every scenario whose tags match the expression, or
+  /// every scenario without one, which fails the scenario if it panics
+  ///
+  /// Registrars that cannot run hooks panic, which is what this does by
+  /// default, rather than leave them out of the run unnoticed.
+  #[allow(unused_variables)]
+  fn before(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    panic!("Hooks are not supported by this registrar, so the hook at {}:{} cannot be added",
+           file,
+           line)
+  }
+  /// Add a hook run after every scenario whose tags match the expression, or
+  /// every scenario without one, even one that failed
+  ///
+  /// Registrars that cannot run hooks panic, as for `before`.
+  #[allow(unused_variables)]
+  fn after(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    panic!("Hooks are not supported by this registrar, so the hook at {}:{} cannot be added",
+           file,
+           line)
+  }
+  /// Add a hook run once after the last scenario of the run, even when fail
+  /// fast stopped the run early, which fails the run if it panics
+  ///
+  /// Registrars that cannot run hooks panic, as for `before`.
+  #[allow(unused_variables)]
+  fn after_all(&mut self, file: &str, line: u32, hook: RunHook<World>) {
+    panic!("Hooks are not supported by this registrar, so the hook at {}:{} cannot be added",
+           file,
+           line)
+  }
+
+  /// Add a step with a keyword and an optional timeout, as the step macros do
+  ///
+  /// The macros go through this so that they only evaluate the registrar and
+  /// the regular expression once.
+  #[doc(hidden)]
+  fn register_step(&mut self,
+                   keyword: StepKeyword,
+                   file: &str,
+                   line: u32,
+                   regex: Regex,
+                   timeout: Option<Duration>,
+                   step: SimpleStep<World>) {
+    let pattern = regex.as_str().to_owned();
+    match keyword {
+      StepKeyword::Given => self.given(file, line, regex, step),
+      StepKeyword::When => self.when(file, line, regex, step),
+      StepKeyword::Then => self.then(file, line, regex, step),
+    }
+    if let Some(timeout) = timeout {
+      self.timeout(&pattern, timeout);
+    }
+    self.checks_arguments(&pattern);
+  }
 }
 
 impl<World> CucumberRegistrar<World> for Cucumber<World> {
   fn given(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
-    self.insert_step(format!("{}:{}", file, line), regex, step)
+    self.insert_keyword_step(StepKeyword::Given, format!("{}:{}", file, line), regex, step);
   }
 
   fn when(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
-    self.insert_step(format!("{}:{}", file, line), regex, step)
+    self.insert_keyword_step(StepKeyword::When, format!("{}:{}", file, line), regex, step);
   }
 
   fn then(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
-    self.insert_step(format!("{}:{}", file, line), regex, step)
+    self.insert_keyword_step(StepKeyword::Then, format!("{}:{}", file, line), regex, step);
+  }
+
+  fn timeout(&mut self, regex: &str, timeout: Duration) {
+    self.set_timeout(regex, timeout)
+  }
+
+  fn checks_arguments(&mut self, regex: &str) {
+    self.set_checks_arguments(regex)
+  }
+
+  fn before(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.insert_hook(HookKind::Before, format!("{}:{}", file, line), tags, hook);
+  }
+
+  fn after(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.insert_hook(HookKind::After, format!("{}:{}", file, line), tags, hook);
+  }
+
+  fn after_all(&mut self, file: &str, line: u32, hook: RunHook<World>) {
+    self.insert_after_all_hook(format!("{}:{}", file, line), hook);
   }
 }
 
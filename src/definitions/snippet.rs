@@ -0,0 +1,327 @@
+//! Logic for generating step definition snippets for undefined steps
+//!
+//! Snippets use the [Given!](../../macro.Given!.html),
+//! [When!](../../macro.When!.html) and [Then!](../../macro.Then!.html)
+//! macros, and are intended to compile once pasted into a step registration
+//! function.
+
+use regex;
+
+/// The way snippets describe the steps they match
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnippetStyle {
+  /// A regular expression, such as `^I have (-?\d+) cucumbers$`
+  Regex,
+  /// A [Cucumber Expression](../../cucumber_regex/fn.expression.html), such
+  /// as `I have {int} cucumbers`
+  Expression,
+}
+
+impl Default for SnippetStyle {
+  fn default() -> SnippetStyle {
+    SnippetStyle::Regex
+  }
+}
+
+/// Pieces of a step name, as far as snippet generation is concerned
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+  Literal(String),
+  Quoted,
+  Integer,
+  Float,
+}
+
+impl Token {
+  fn regex(&self) -> String {
+    match self {
+      &Token::Literal(ref text) => regex::quote(text),
+      &Token::Quoted => "\"([^\"]*)\"".to_owned(),
+      &Token::Integer => "(-?\\d+)".to_owned(),
+      &Token::Float => "(-?\\d+\\.\\d+)".to_owned(),
+    }
+  }
+
+  fn expression(&self) -> String {
+    match self {
+      &Token::Literal(ref text) => {
+        text.chars().fold(String::new(), |mut escaped, c| {
+          if "\\{(/".contains(c) {
+            escaped.push('\\');
+          }
+          escaped.push(c);
+          escaped
+        })
+      },
+      &Token::Quoted => "{string}".to_owned(),
+      &Token::Integer => "{int}".to_owned(),
+      &Token::Float => "{float}".to_owned(),
+    }
+  }
+
+  fn arg_type(&self) -> Option<&'static str> {
+    match self {
+      &Token::Literal(_) => None,
+      &Token::Quoted => Some("String"),
+      &Token::Integer => Some("i32"),
+      &Token::Float => Some("f64"),
+    }
+  }
+}
+
+/// Generate a snippet for an undefined step
+///
+/// The keyword is mapped onto the matching step macro (`And`, `But` and `*`
+/// become `Given!`). Quoted strings and numbers in the step name are replaced
+/// by capture groups or parameters, depending on the style, with a typed
+/// argument for each, and a table or docstring argument is added when the
+/// step has one according to `multiline_arg_class`.
+///
+/// # Example
+///
+/// ```
+/// use cucumber::definitions::snippet::{self, SnippetStyle};
+///
+/// fn main() {
+///   let text = snippet::snippet_text(SnippetStyle::Regex, "Given", "I have 5 \"gold\" coins", "");
+///   assert!(text.contains("Given!(cuke, \"^I have (-?\\\\d+) \\\"([^\\\"]*)\\\" coins$\""));
+///   assert!(text.contains("(arg1, arg2): (i32, String)"));
+///
+///   let text =
+///     snippet::snippet_text(SnippetStyle::Expression, "Given", "I have 5 \"gold\" coins", "");
+///   assert!(text.contains("Given!(cuke, &cucumber_regex::expression(\"I have {int} {string} \
+///                          coins\")"));
+/// }
+/// ```
+pub fn snippet_text(style: SnippetStyle,
+                    step_keyword: &str,
+                    step_name: &str,
+                    multiline_arg_class: &str)
+                    -> String {
+  let tokens = tokenize(step_name);
+
+  let pattern = match style {
+    SnippetStyle::Regex => {
+      format!("\"^{}$\"",
+              escape_str(&tokens.iter().map(|token| token.regex()).collect::<Vec<_>>().concat()))
+    },
+    SnippetStyle::Expression => {
+      format!("&cucumber_regex::expression(\"{}\")",
+              escape_str(&tokens.iter()
+                .map(|token| token.expression())
+                .collect::<Vec<_>>()
+                .concat()))
+    },
+  };
+  let imports = match style {
+    SnippetStyle::Regex => "Cucumber, CucumberRegistrar",
+    SnippetStyle::Expression => "cucumber_regex, Cucumber, CucumberRegistrar",
+  };
+
+  let mut arg_types: Vec<&'static str> =
+    tokens.iter().filter_map(|token| token.arg_type()).collect();
+  let mut arg_names: Vec<String> =
+    (1..arg_types.len() + 1).map(|idx| format!("arg{}", idx)).collect();
+
+  match multiline_arg_type(multiline_arg_class) {
+    Some((name, arg_type)) => {
+      arg_names.push(name.to_owned());
+      arg_types.push(arg_type);
+    },
+    None => {},
+  }
+
+  format!("  // In a step registration function taking cuke: &mut \
+           CucumberRegistrar<YourWorld>\n  // use cucumber::{{{}}};\n  {}!(cuke, {}, |c: \
+           &Cucumber<YourWorld>, world: &mut YourWorld, {}| {{\n    c.pending(\"TODO\")\n  \
+           }});",
+          imports,
+          macro_name(step_keyword),
+          pattern,
+          args_pattern(&arg_names, &arg_types))
+}
+
+fn macro_name(step_keyword: &str) -> &'static str {
+  match step_keyword.trim() {
+    "When" => "When",
+    "Then" => "Then",
+    _ => "Given",
+  }
+}
+
+fn multiline_arg_type(multiline_arg_class: &str) -> Option<(&'static str, &'static str)> {
+  if multiline_arg_class.ends_with("DataTable") || multiline_arg_class.ends_with("Table") {
+    Some(("table", "Vec<Vec<String>>"))
+  } else if multiline_arg_class.ends_with("DocString") {
+    Some(("docstring", "String"))
+  } else {
+    None
+  }
+}
+
+// Builds the destructuring pattern and type for the step arguments, keeping in
+// mind that a tuple of one value is written as `(t,): (Type,)`
+fn args_pattern(names: &[String], types: &[&str]) -> String {
+  match names.len() {
+    0 => "()".to_owned(),
+    1 => format!("({},): ({},)", names[0], types[0]),
+    _ => format!("({}): ({})", names.join(", "), types.join(", ")),
+  }
+}
+
+// Escapes a string for inclusion in a Rust string literal
+fn escape_str(text: &str) -> String {
+  text.replace("\\", "\\\\").replace("\"", "\\\"")
+}
+
+fn tokenize(step_name: &str) -> Vec<Token> {
+  let chars: Vec<char> = step_name.chars().collect();
+  let mut tokens = Vec::new();
+  let mut literal = String::new();
+  let mut idx = 0;
+
+  while idx < chars.len() {
+    let c = chars[idx];
+    let at_word_start = idx == 0 || !chars[idx - 1].is_alphanumeric();
+
+    if c == '"' {
+      if let Some(len) = chars[idx + 1..].iter().position(|&c| c == '"') {
+        push_literal(&mut tokens, &mut literal);
+        tokens.push(Token::Quoted);
+        idx += len + 2;
+        continue;
+      }
+    } else if at_word_start && (c.is_digit(10) || (c == '-' && is_digit_at(&chars, idx + 1))) {
+      if let Some((token, len)) = number_at(&chars, idx) {
+        push_literal(&mut tokens, &mut literal);
+        tokens.push(token);
+        idx += len;
+        continue;
+      }
+    }
+
+    literal.push(c);
+    idx += 1;
+  }
+
+  push_literal(&mut tokens, &mut literal);
+  tokens
+}
+
+fn push_literal(tokens: &mut Vec<Token>, literal: &mut String) {
+  if !literal.is_empty() {
+    tokens.push(Token::Literal(literal.clone()));
+    literal.clear();
+  }
+}
+
+fn is_digit_at(chars: &[char], idx: usize) -> bool {
+  chars.get(idx).map(|c| c.is_digit(10)).unwrap_or(false)
+}
+
+// Reads a whole number starting at idx, if it isn't part of a larger word
+fn number_at(chars: &[char], start: usize) -> Option<(Token, usize)> {
+  let mut idx = start + 1;
+  while is_digit_at(chars, idx) {
+    idx += 1;
+  }
+
+  let mut token = Token::Integer;
+  if chars.get(idx) == Some(&'.') && is_digit_at(chars, idx + 1) {
+    idx += 1;
+    while is_digit_at(chars, idx) {
+      idx += 1;
+    }
+    token = Token::Float;
+  }
+
+  match chars.get(idx) {
+    Some(c) if c.is_alphanumeric() => None,
+    _ => Some((token, idx - start)),
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use super::{Token, tokenize};
+
+  #[test]
+  fn it_tokenizes_quoted_strings_and_numbers() {
+    assert_eq!(tokenize("I have 5 \"gold\" coins worth 1.5 each"),
+               vec![Token::Literal("I have ".to_owned()),
+                    Token::Integer,
+                    Token::Literal(" ".to_owned()),
+                    Token::Quoted,
+                    Token::Literal(" coins worth ".to_owned()),
+                    Token::Float,
+                    Token::Literal(" each".to_owned())]);
+  }
+
+  #[test]
+  fn it_leaves_numbers_within_words() {
+    assert_eq!(tokenize("the h2o is -3 degrees"),
+               vec![Token::Literal("the h2o is ".to_owned()),
+                    Token::Integer,
+                    Token::Literal(" degrees".to_owned())]);
+  }
+
+  #[test]
+  fn it_leaves_unterminated_quotes() {
+    assert_eq!(tokenize("a \"quote"),
+               vec![Token::Literal("a \"quote".to_owned())]);
+  }
+
+  #[test]
+  fn it_generates_a_snippet_without_arguments() {
+    assert_eq!(snippet_text(SnippetStyle::Regex, "When", "I press (+)", ""),
+               "  // In a step registration function taking cuke: &mut \
+                CucumberRegistrar<YourWorld>\n  // use cucumber::{Cucumber, CucumberRegistrar};\n  \
+                When!(cuke, \"^I press \\\\(\\\\+\\\\)$\", |c: &Cucumber<YourWorld>, world: &mut \
+                YourWorld, ()| {\n    c.pending(\"TODO\")\n  });");
+  }
+
+  #[test]
+  fn it_generates_typed_arguments() {
+    let text = snippet_text(SnippetStyle::Regex, "Then", "the total is 5", "");
+    assert!(text.contains("Then!(cuke, \"^the total is (-?\\\\d+)$\""));
+    assert!(text.contains("(arg1,): (i32,)"));
+  }
+
+  #[test]
+  fn it_adds_multiline_arguments() {
+    let text = snippet_text(SnippetStyle::Regex,
+                            "Given",
+                            "the \"users\" table",
+                            "Cucumber::MultilineArgument::DataTable");
+    assert!(text.contains("(arg1, table): (String, Vec<Vec<String>>)"));
+
+    let text = snippet_text(SnippetStyle::Regex,
+                            "Given",
+                            "the text",
+                            "Cucumber::MultilineArgument::DocString");
+    assert!(text.contains("(docstring,): (String,)"));
+  }
+
+  #[test]
+  fn it_generates_expression_snippets() {
+    assert_eq!(snippet_text(SnippetStyle::Expression,
+                            "Given",
+                            "I have 5 \"gold\" coins worth 1.5 (each/all)",
+                            ""),
+               "  // In a step registration function taking cuke: &mut \
+                CucumberRegistrar<YourWorld>\n  // use cucumber::{cucumber_regex, Cucumber, \
+                CucumberRegistrar};\n  Given!(cuke, &cucumber_regex::expression(\"I have {int} \
+                {string} coins worth {float} \\\\(each\\\\/all)\"), |c: &Cucumber<YourWorld>, \
+                world: &mut YourWorld, (arg1, arg2, arg3): (i32, String, f64)| {\n    \
+                c.pending(\"TODO\")\n  });");
+  }
+
+  #[test]
+  fn it_maps_conjunctions_to_given() {
+    assert!(snippet_text(SnippetStyle::Regex, "And ", "a step", "")
+      .contains("Given!(cuke, \"^a step$\""));
+    assert!(snippet_text(SnippetStyle::Regex, "*", "a step", "")
+      .contains("Given!(cuke, \"^a step$\""));
+  }
+}
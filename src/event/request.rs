@@ -14,9 +14,10 @@ include!(concat!(env!("OUT_DIR"), "/event/request.rs"));
 
 use std::ascii::AsciiExt;
 
-use serde::Deserializer;
+use serde::{Deserializer, Serializer};
 use serde::de::{SeqVisitor, Visitor};
 use serde::de::impls::VecVisitor;
+use serde::ser::impls::TupleVisitor2;
 use serde::Error as SerdeError;
 
 use event::response::StepArg;
@@ -44,6 +45,26 @@ impl Deserialize for Request {
   }
 }
 
+impl Serialize for Request {
+  fn serialize<S: Serializer>(&self, s: &mut S) -> Result<(), S::Error> {
+    match self {
+      &Request::StepMatches(ref payload) => {
+        s.serialize_seq(TupleVisitor2::new(&("step_matches", payload)))
+      },
+      &Request::Invoke(ref payload) => s.serialize_seq(TupleVisitor2::new(&("invoke", payload))),
+      &Request::BeginScenario(ref payload) => {
+        s.serialize_seq(TupleVisitor2::new(&("begin_scenario", payload)))
+      },
+      &Request::EndScenario(ref payload) => {
+        s.serialize_seq(TupleVisitor2::new(&("end_scenario", payload)))
+      },
+      &Request::SnippetText(ref payload) => {
+        s.serialize_seq(TupleVisitor2::new(&("snippet_text", payload)))
+      },
+    }
+  }
+}
+
 
 struct RequestVisitor;
 
@@ -136,6 +157,16 @@ impl Deserialize for InvokeArgument {
   }
 }
 
+impl Serialize for InvokeArgument {
+  fn serialize<S: Serializer>(&self, s: &mut S) -> Result<(), S::Error> {
+    match self {
+      &InvokeArgument::String(ref val) => s.serialize_str(val),
+      &InvokeArgument::None => s.serialize_unit(),
+      &InvokeArgument::Table(ref rows) => rows.serialize(s),
+    }
+  }
+}
+
 struct InvokeArgumentVisitor;
 
 impl Visitor for InvokeArgumentVisitor {
@@ -291,4 +322,35 @@ mod test {
       _ => panic!("result was not SnippetText type"),
     }
   }
+
+  #[test]
+  fn write_begin_scenario() {
+    let request = Request::BeginScenario(BeginScenarioRequest { tags: vec!["hello".to_owned()] });
+    let string = serde_json::to_string(&request);
+    assert_eq!(string.unwrap(), "[\"begin_scenario\",{\"tags\":[\"hello\"]}]");
+  }
+
+  #[test]
+  fn write_invoke_complicated_args() {
+    let request = Request::Invoke(InvokeRequest {
+      id: "1".to_owned(),
+      args: vec![InvokeArgument::from_str("we're"),
+                 InvokeArgument::None,
+                 InvokeArgument::Table(vec![vec!["wired".to_owned()]])],
+    });
+    let string = serde_json::to_string(&request);
+    assert_eq!(string.unwrap(),
+               "[\"invoke\",{\"id\":\"1\",\"args\":[\"we're\",null,[[\"wired\"]]]}]");
+  }
+
+  #[test]
+  fn written_requests_read_back() {
+    let request = Request::SnippetText(SnippetTextRequest {
+      step_keyword: "Given".to_owned(),
+      multiline_arg_class: "".to_owned(),
+      step_name: "we're all wired".to_owned(),
+    });
+    let string = serde_json::to_string(&request).unwrap();
+    assert_eq!(serde_json::from_str::<Request>(&string).unwrap(), request);
+  }
 }
@@ -11,13 +11,19 @@ include!("response.rs.in");
 include!(concat!(env!("OUT_DIR"), "/event/response.rs"));
 
 use serde;
+use serde::Deserializer;
+use serde::de::{SeqVisitor, Visitor};
+use serde::de::impls::VecVisitor;
 use serde::ser::impls::TupleVisitor2;
 use serde::ser::MapVisitor;
+use serde::Error as SerdeError;
 use std::fmt::Debug;
 
-// NOTE: These defined in response.rs.in (as they need to derive Serialize)
+// NOTE: These defined in response.rs.in (as they need to derive Serialize or
+// Deserialize)
 // pub struct Step
 // pub struct FailMessage
+// pub struct StepArg
 
 /// Types of responses produced by
 /// [runners](../../runner/struct.WorldRunner.html)
@@ -28,6 +34,8 @@ pub enum Response {
   BeginScenario,
   EndScenario,
   SnippetText(String),
+  /// A protocol level failure, used when a request could not be handled at all
+  Fail(FailMessage),
 }
 
 impl Serialize for Response {
@@ -57,14 +65,95 @@ impl Serialize for Response {
       &Response::SnippetText(ref text) => {
         s.serialize_seq(TupleVisitor2::new(&("success", text.clone())))
       },
+      &Response::Fail(ref message) => s.serialize_seq(TupleVisitor2::new(&("fail", message))),
     }
   }
 }
 
-#[derive(Eq, PartialEq, Clone, Debug)]
-pub struct StepArg {
-  pub val: Option<String>,
-  pub pos: Option<u32>,
+/// Reads a response as written by a wire server
+///
+/// The wire protocol answers `begin_scenario`, `end_scenario` and successful
+/// `invoke` requests identically, so a bare `["success"]` is read as
+/// `Response::Invoke(InvokeResponse::Success)`, and any `fail` as
+/// `Response::Invoke(InvokeResponse::Fail(..))`. Consumers that know which
+/// request was made, like the [WireClient](../../client/struct.WireClient.html),
+/// should reinterpret these accordingly.
+impl Deserialize for Response {
+  fn deserialize<D: Deserializer>(d: &mut D) -> Result<Self, D::Error> {
+    d.deserialize(ResponseVisitor)
+  }
+}
+
+struct ResponseVisitor;
+
+impl Visitor for ResponseVisitor {
+  type Value = Response;
+
+  fn visit_seq<V: SeqVisitor>(&mut self, mut _visitor: V) -> Result<Response, V::Error> {
+    let status = try!(_visitor.visit::<String>());
+
+    match status {
+      None => Err(V::Error::invalid_length(0)),
+      Some(status) => {
+        match status.as_ref() {
+          "success" => {
+            let payload = try!(_visitor.visit::<SuccessPayload>());
+            try!(_visitor.end());
+            match payload {
+              None => Ok(Response::Invoke(InvokeResponse::Success)),
+              Some(SuccessPayload::Steps(steps)) => {
+                if steps.len() == 0 {
+                  Ok(Response::StepMatches(StepMatchesResponse::NoMatch))
+                } else {
+                  Ok(Response::StepMatches(StepMatchesResponse::Match(steps)))
+                }
+              },
+              Some(SuccessPayload::Text(text)) => Ok(Response::SnippetText(text)),
+            }
+          },
+          "pending" => {
+            let message = try!(_visitor.visit::<String>());
+            try!(_visitor.end());
+            Ok(Response::Invoke(InvokeResponse::Pending(message.unwrap_or(String::new()))))
+          },
+          "fail" => {
+            let message = try!(_visitor.visit::<FailMessage>());
+            try!(_visitor.end());
+            let message = message.unwrap_or(FailMessage::new(String::new()));
+            Ok(Response::Invoke(InvokeResponse::Fail(message)))
+          },
+          _ => Err(V::Error::custom("Unknown response status as first value")),
+        }
+      },
+    }
+  }
+}
+
+// The second value of a "success" response, which is either a list of step
+// matches or a snippet
+enum SuccessPayload {
+  Steps(Vec<Step>),
+  Text(String),
+}
+
+impl Deserialize for SuccessPayload {
+  fn deserialize<D: Deserializer>(d: &mut D) -> Result<Self, D::Error> {
+    d.deserialize(SuccessPayloadVisitor)
+  }
+}
+
+struct SuccessPayloadVisitor;
+
+impl Visitor for SuccessPayloadVisitor {
+  type Value = SuccessPayload;
+
+  fn visit_str<E: SerdeError>(&mut self, v: &str) -> Result<SuccessPayload, E> {
+    Ok(SuccessPayload::Text(v.to_owned()))
+  }
+
+  fn visit_seq<V: SeqVisitor>(&mut self, _visitor: V) -> Result<SuccessPayload, V::Error> {
+    VecVisitor::new().visit_seq(_visitor).map(|res| SuccessPayload::Steps(res))
+  }
 }
 
 impl Serialize for StepArg {
@@ -350,10 +439,72 @@ mod test {
     assert_eq!(string.unwrap(), "[\"success\"]");
   }
 
+  #[test]
+  fn it_serializes_protocol_fail() {
+    let response = Response::Fail(FailMessage::new("bad request".to_owned()));
+    let string = serde_json::to_string(&response);
+    assert_eq!(string.unwrap(),
+               "[\"fail\",{\"message\":\"bad request\",\"exception\":\"\"}]");
+  }
+
   #[test]
   fn it_serializes_snippet_text() {
     let response = Response::SnippetText("Snippet".to_owned());
     let string = serde_json::to_string(&response);
     assert_eq!(string.unwrap(), "[\"success\",\"Snippet\"]");
   }
+
+  #[test]
+  fn it_deserializes_step_matches() {
+    let json = "[\"success\",[{\"id\":\"1\",\"args\":[{\"val\":\"arg\",\"pos\":0},\
+                {\"val\":null,\"pos\":null}],\"source\":\"test\"}]]";
+    let response = serde_json::from_str::<Response>(json).unwrap();
+    let step = Step {
+      id: "1".to_owned(),
+      args: vec![StepArg {
+                   val: Some("arg".to_owned()),
+                   pos: Some(0),
+                 },
+                 StepArg {
+                   val: None,
+                   pos: None,
+                 }],
+      source: "test".to_owned(),
+    };
+    assert_eq!(response,
+               Response::StepMatches(StepMatchesResponse::Match(vec![step])));
+  }
+
+  #[test]
+  fn it_deserializes_step_matches_no_match() {
+    let response = serde_json::from_str::<Response>("[\"success\",[]]").unwrap();
+    assert_eq!(response, Response::StepMatches(StepMatchesResponse::NoMatch));
+  }
+
+  #[test]
+  fn it_deserializes_bare_success_as_invoke() {
+    let response = serde_json::from_str::<Response>("[\"success\"]").unwrap();
+    assert_eq!(response, Response::Invoke(InvokeResponse::Success));
+  }
+
+  #[test]
+  fn it_deserializes_invoke_pending() {
+    let response = serde_json::from_str::<Response>("[\"pending\",\"later\"]").unwrap();
+    assert_eq!(response,
+               Response::Invoke(InvokeResponse::pending_from_str("later")));
+  }
+
+  #[test]
+  fn it_deserializes_invoke_fail() {
+    let response = serde_json::from_str::<Response>("[\"fail\",{\"message\":\"broken\"}]")
+      .unwrap();
+    assert_eq!(response,
+               Response::Invoke(InvokeResponse::fail_from_str("broken")));
+  }
+
+  #[test]
+  fn it_deserializes_snippet_text() {
+    let response = serde_json::from_str::<Response>("[\"success\",\"Snippet\"]").unwrap();
+    assert_eq!(response, Response::SnippetText("Snippet".to_owned()));
+  }
 }
@@ -0,0 +1,331 @@
+//! Cucumber JSON reports, as read by tools such as cucumber-html-reporter
+
+#[cfg(feature = "serde_macros")]
+include!("json.rs.in");
+
+#[cfg(not(feature = "serde_macros"))]
+include!(concat!(env!("OUT_DIR"), "/format/json.rs"));
+
+use std::io::Write;
+use std::mem;
+use std::time::Duration;
+
+use serde_json;
+
+use definitions::registration::HookKind;
+use event::response::InvokeResponse;
+use format::Formatter;
+use gherkin::{Feature, Scenario, Step, StepArgument};
+use native::{HookResult, ScenarioResult, StepResult, Summary};
+
+/// Writes a Cucumber JSON report once the run is over
+///
+/// Every row of a scenario outline's examples is reported as a scenario of its
+/// own. A retried scenario is reported once, by its last attempt, which has
+/// its `attempt` number and the elements of the failed attempts before it in
+/// `earlier_attempts`. Anything the steps printed is included as their
+/// `output` if output was captured.
+pub struct Json<W: Write> {
+  out: W,
+  features: Vec<JsonFeature>,
+  // Where the current scenario is in the feature's elements, and how many of
+  // its steps have finished
+  scenario_index: usize,
+  steps_finished: usize,
+  // The elements of the failed attempts of the scenario being retried
+  earlier_attempts: Vec<JsonElement>,
+}
+
+impl<W: Write> Json<W> {
+  pub fn new(out: W) -> Json<W> {
+    Json {
+      out: out,
+      features: Vec::new(),
+      scenario_index: 0,
+      steps_finished: 0,
+      earlier_attempts: Vec::new(),
+    }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  fn elements(&mut self) -> &mut Vec<JsonElement> {
+    &mut self.features.last_mut().expect("Scenario reported outside of a feature").elements
+  }
+}
+
+// Lower case, with spaces replaced by dashes, as in the ids Cucumber gives
+fn slug(name: &str) -> String {
+  name.to_lowercase().split_whitespace().collect::<Vec<_>>().join("-")
+}
+
+fn tags(tags: &[String]) -> Vec<JsonTag> {
+  tags.iter().map(|tag| JsonTag { name: tag.clone() }).collect()
+}
+
+fn nanoseconds(duration: Duration) -> u64 {
+  duration.as_secs() * 1000000000 + duration.subsec_nanos() as u64
+}
+
+fn error_message(response: Option<&InvokeResponse>) -> Option<String> {
+  match response {
+    Some(&InvokeResponse::Fail(ref failure)) if failure.exception().is_empty() => {
+      Some(failure.message().to_owned())
+    },
+    Some(&InvokeResponse::Fail(ref failure)) => {
+      Some(format!("{} ({})", failure.message(), failure.exception()))
+    },
+    Some(&InvokeResponse::Pending(ref pending)) => Some(format!("{} (pending)", pending)),
+    _ => None,
+  }
+}
+
+fn json_step(step: &Step, result: &StepResult) -> JsonStep {
+  let (doc_string, rows) = match step.argument {
+    Some(StepArgument::DocString(ref value)) => {
+      let doc_string = JsonDocString {
+        value: value.clone(),
+        line: step.line + 1,
+      };
+      (Some(doc_string), None)
+    },
+    Some(StepArgument::Table(ref table)) => {
+      let rows = table.iter().map(|row| JsonRow { cells: row.clone() }).collect();
+      (None, Some(rows))
+    },
+    None => (None, None),
+  };
+
+  JsonStep {
+    keyword: format!("{} ", step.keyword),
+    name: step.text.clone(),
+    line: step.line,
+    matched: JsonMatch { location: result.matched.as_ref().map(|matched| matched.source.clone()) },
+    result: JsonResult {
+      status: result.status.as_str().to_owned(),
+      duration: nanoseconds(result.duration),
+      error_message: error_message(result.response.as_ref()),
+    },
+    doc_string: doc_string,
+    rows: rows,
+    output: result.output.lines().map(|line| line.to_owned()).collect(),
+  }
+}
+
+impl<W: Write> Formatter for Json<W> {
+  fn feature_started(&mut self, feature: &Feature) {
+    self.features.push(JsonFeature {
+      uri: feature.path.clone(),
+      id: slug(&feature.name),
+      keyword: "Feature".to_owned(),
+      name: feature.name.clone(),
+      description: feature.description.clone(),
+      line: feature.line,
+      tags: tags(&feature.tags),
+      elements: Vec::new(),
+    });
+  }
+
+  fn scenario_started(&mut self, feature: &Feature, scenario: &Scenario) {
+    let id = match scenario.outline_line {
+      Some(_) => format!("{};{};{}", slug(&feature.name), slug(&scenario.name), scenario.line),
+      None => format!("{};{}", slug(&feature.name), slug(&scenario.name)),
+    };
+
+    if let Some(line) = feature.background_line.or(scenario.rule_background_line) {
+      self.elements().push(JsonElement {
+        id: format!("{};background", slug(&feature.name)),
+        keyword: "Background".to_owned(),
+        kind: "background".to_owned(),
+        name: String::new(),
+        description: String::new(),
+        line: line,
+        tags: Vec::new(),
+        before: Vec::new(),
+        steps: Vec::new(),
+        after: Vec::new(),
+        attempt: None,
+        earlier_attempts: Vec::new(),
+      });
+    }
+    self.elements().push(JsonElement {
+      id: id,
+      keyword: scenario.keyword.clone(),
+      kind: "scenario".to_owned(),
+      name: scenario.name.clone(),
+      description: String::new(),
+      line: scenario.line,
+      tags: tags(&feature.scenario_tags(scenario)),
+      before: Vec::new(),
+      steps: Vec::new(),
+      after: Vec::new(),
+      attempt: None,
+      earlier_attempts: Vec::new(),
+    });
+    self.scenario_index = self.elements().len() - 1;
+    self.steps_finished = 0;
+  }
+
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    let hook = JsonHook {
+      matched: JsonMatch { location: Some(result.source.clone()) },
+      result: JsonResult {
+        status: result.status.as_str().to_owned(),
+        duration: nanoseconds(result.duration),
+        error_message: error_message(Some(&result.response)),
+      },
+    };
+    let index = self.scenario_index;
+    let element = &mut self.elements()[index];
+    match result.kind {
+      HookKind::Before => element.before.push(hook),
+      HookKind::After => element.after.push(hook),
+    }
+  }
+
+  fn step_finished(&mut self,
+                   feature: &Feature,
+                   scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    // NOTE: Background steps, of the feature and then of the rule, are
+    // reported first, and belong to the element just before the scenario's
+    let index = if self.steps_finished < feature.background_steps(scenario).len() {
+      self.scenario_index - 1
+    } else {
+      self.scenario_index
+    };
+    self.steps_finished += 1;
+    self.elements()[index].steps.push(json_step(step, result));
+  }
+
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    if result.attempt == 1 && !result.retried {
+      return;
+    }
+    // The background element of the attempt is moved along with it
+    let first = match feature.background_line.or(scenario.rule_background_line) {
+      Some(_) => self.scenario_index - 1,
+      None => self.scenario_index,
+    };
+    for element in &mut self.elements()[first..] {
+      element.attempt = Some(result.attempt);
+    }
+    if result.retried {
+      let attempt = self.elements().split_off(first);
+      self.earlier_attempts.extend(attempt);
+    } else {
+      let index = self.scenario_index;
+      let earlier_attempts = mem::replace(&mut self.earlier_attempts, Vec::new());
+      self.elements()[index].earlier_attempts = earlier_attempts;
+    }
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn run_finished(&mut self, _summary: &Summary) {
+    let _ = writeln!(self.out, "{}", serde_json::to_string_pretty(&self.features).unwrap());
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use serde_json::{self, Value};
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use native::test::{flaky_step, formatter_runner, run_features, run_with};
+
+  fn report() -> Value {
+    let source = "@json\nFeature: Cucumber JSON\n  Background:\n    Given I have 1\n  @fast\n  \
+                  Scenario: Breaking\n    Given I have 2\n      \"\"\"\n      text\n      \
+                  \"\"\"\n    Then it breaks\n    Then I have 3\n      | a | b |\n";
+    let report = run_features(&mut formatter_runner(),
+                              &[("features/test.feature", source)],
+                              Json::new);
+    serde_json::from_str(&report).unwrap()
+  }
+
+  fn string(report: &Value, pointer: &str) -> Option<String> {
+    report.pointer(pointer).and_then(Value::as_string).map(|value| value.to_owned())
+  }
+
+  fn number(report: &Value, pointer: &str) -> Option<u64> {
+    report.pointer(pointer).and_then(Value::as_u64)
+  }
+
+  #[test]
+  fn it_reports_features() {
+    let report = report();
+    assert_eq!(string(&report, "/0/uri"), Some("features/test.feature".to_owned()));
+    assert_eq!(string(&report, "/0/id"), Some("cucumber-json".to_owned()));
+    assert_eq!(number(&report, "/0/line"), Some(2));
+    assert_eq!(string(&report, "/0/tags/0/name"), Some("@json".to_owned()));
+  }
+
+  #[test]
+  fn it_reports_backgrounds_before_each_scenario() {
+    let report = report();
+    assert_eq!(report.pointer("/0/elements").and_then(Value::as_array).map(|e| e.len()),
+               Some(2));
+    assert_eq!(string(&report, "/0/elements/0/type"), Some("background".to_owned()));
+    assert_eq!(number(&report, "/0/elements/0/line"), Some(3));
+    assert_eq!(string(&report, "/0/elements/0/steps/0/name"), Some("I have 1".to_owned()));
+    assert_eq!(string(&report, "/0/elements/1/type"), Some("scenario".to_owned()));
+    assert_eq!(string(&report, "/0/elements/1/id"),
+               Some("cucumber-json;breaking".to_owned()));
+    assert_eq!(string(&report, "/0/elements/1/tags/1/name"), Some("@fast".to_owned()));
+    assert_eq!(report.pointer("/0/elements/1/steps").and_then(Value::as_array).map(|s| s.len()),
+               Some(3));
+  }
+
+  #[test]
+  fn it_reports_steps() {
+    let report = report();
+    let steps = report.pointer("/0/elements/1/steps").unwrap();
+
+    assert_eq!(string(steps, "/0/keyword"), Some("Given ".to_owned()));
+    assert_eq!(string(steps, "/0/match/location"), Some("steps.rs:1".to_owned()));
+    assert_eq!(string(steps, "/0/result/status"), Some("passed".to_owned()));
+    assert!(number(steps, "/0/result/duration").is_some());
+    assert_eq!(string(steps, "/0/doc_string/value"), Some("text".to_owned()));
+    assert_eq!(string(steps, "/1/result/status"), Some("failed".to_owned()));
+    assert_eq!(string(steps, "/1/result/error_message"), Some("it broke".to_owned()));
+    assert_eq!(string(steps, "/2/result/status"), Some("skipped".to_owned()));
+    assert_eq!(string(steps, "/2/rows/0/cells/1"), Some("b".to_owned()));
+    assert_eq!(steps.pointer("/2/result/error_message"), None);
+  }
+
+  #[test]
+  fn it_reports_the_last_attempt_of_retried_scenarios() {
+    let mut runner = formatter_runner().retry(1);
+    runner.then("steps.rs", 5, cucumber_regex::build("^it is flaky$"), flaky_step());
+
+    let report = run_with(&mut runner,
+                          "Feature: Retries\n  Background:\n    Given I have 1\n  Scenario: \
+                           Flaky\n    Then it is flaky\n  Scenario: Once\n    Given I have 2\n",
+                          Json::new);
+    let report: Value = serde_json::from_str(&report).unwrap();
+
+    let elements = report.pointer("/0/elements").and_then(Value::as_array).unwrap();
+    assert_eq!(elements.len(), 4);
+    let flaky = &elements[1];
+    assert_eq!(number(flaky, "/attempt"), Some(2));
+    assert_eq!(string(flaky, "/steps/0/result/status"), Some("passed".to_owned()));
+    assert_eq!(string(flaky, "/earlier_attempts/0/type"), Some("background".to_owned()));
+    assert_eq!(number(flaky, "/earlier_attempts/1/attempt"), Some(1));
+    assert_eq!(string(flaky, "/earlier_attempts/1/steps/0/result/status"),
+               Some("failed".to_owned()));
+    assert_eq!(flaky.pointer("/earlier_attempts/2"), None);
+    assert_eq!(elements[3].pointer("/attempt"), None);
+    assert_eq!(elements[3].pointer("/earlier_attempts"), None);
+  }
+}
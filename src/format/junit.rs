@@ -0,0 +1,294 @@
+//! JUnit XML reports, as understood by most CI servers
+
+use std::io::Write;
+use std::time::Duration;
+
+use format::{scenario_name, Formatter};
+use gherkin::{Feature, Scenario, Step};
+use event::response::FailMessage;
+use native::{HookResult, ScenarioResult, StepResult, StepStatus, Summary};
+
+/// Writes a JUnit XML report once the run is over
+///
+/// Each feature becomes a `<testsuite>`, and each scenario a `<testcase>`,
+/// with every row of a scenario outline's examples as a separate test case.
+/// Failed and ambiguous scenarios are reported as failures, pending and
+/// undefined ones as skipped, and anything the scenario's steps printed goes
+/// to `<system-out>` if output was captured. Attempts of a retried scenario
+/// that failed are reported in its test case as `<flakyFailure>`, if it passed
+/// in the end, or `<rerunFailure>`, as Maven Surefire does.
+pub struct JUnit<W: Write> {
+  out: W,
+  suites: Vec<String>,
+  cases: Vec<String>,
+  counts: SuiteCounts,
+  // A listing of the current scenario's steps, for the details of failures
+  steps: Vec<String>,
+  output: String,
+  // The failure messages and step listings of earlier attempts of the current
+  // scenario
+  attempts: Vec<(String, String)>,
+}
+
+#[derive(Default)]
+struct SuiteCounts {
+  tests: usize,
+  failures: usize,
+  skipped: usize,
+  time: Duration,
+}
+
+impl<W: Write> JUnit<W> {
+  pub fn new(out: W) -> JUnit<W> {
+    JUnit {
+      out: out,
+      suites: Vec::new(),
+      cases: Vec::new(),
+      counts: SuiteCounts::default(),
+      steps: Vec::new(),
+      output: String::new(),
+      attempts: Vec::new(),
+    }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  fn listing(&mut self, text: &str, status: StepStatus, failure: Option<&FailMessage>) {
+    let dots = ".".repeat(60usize.saturating_sub(text.chars().count()));
+    self.steps.push(format!("{}{}{}", text, dots, status.as_str()));
+
+    if let Some(failure) = failure {
+      self.steps.push(format!("\n{}", failure.message()));
+    }
+  }
+}
+
+/// Escape text for use in XML content and attributes
+pub fn escape_xml(text: &str) -> String {
+  text.chars().fold(String::with_capacity(text.len()), |mut escaped, c| {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      c => escaped.push(c),
+    }
+    escaped
+  })
+}
+
+fn cdata(text: &str) -> String {
+  format!("<![CDATA[{}]]>", text.replace("]]>", "]]]]><![CDATA[>"))
+}
+
+// The first line of the first failure of a scenario
+fn failure_message(result: &ScenarioResult) -> String {
+  result.steps
+    .iter()
+    .filter_map(|step| step.failure())
+    .chain(result.hooks.iter().filter_map(|hook| hook.failure()))
+    .map(|failure| failure.message().lines().next().unwrap_or("").to_owned())
+    .next()
+    .unwrap_or(String::new())
+}
+
+fn seconds(duration: Duration) -> String {
+  format!("{}.{:06}", duration.as_secs(), duration.subsec_nanos() / 1000)
+}
+
+impl<W: Write> Formatter for JUnit<W> {
+  fn feature_started(&mut self, _feature: &Feature) {
+    self.cases.clear();
+    self.counts = SuiteCounts::default();
+  }
+
+  fn scenario_started(&mut self, _feature: &Feature, _scenario: &Scenario) {
+    self.steps.clear();
+    self.output.clear();
+  }
+
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    if result.status != StepStatus::Passed {
+      let text = format!("{} hook", result.kind.as_str());
+      self.listing(&text, result.status, result.failure());
+    }
+  }
+
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    let text = format!("{} {}", step.keyword, step.text);
+    self.listing(&text, result.status, result.failure());
+    self.output.push_str(&result.output);
+  }
+
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    self.counts.time += result.duration;
+    if result.retried {
+      self.attempts.push((failure_message(result), self.steps.join("\n")));
+      return;
+    }
+    self.counts.tests += 1;
+
+    let attempts = if result.status == StepStatus::Passed {
+      "flakyFailure"
+    } else {
+      "rerunFailure"
+    };
+    let attempts = self.attempts
+      .drain(..)
+      .map(|(message, steps)| {
+        format!("\n      <{} message=\"{}\" type=\"failed\">{}</{}>",
+                attempts,
+                escape_xml(&message),
+                cdata(&steps),
+                attempts)
+      })
+      .collect::<String>();
+
+    let outcome = match result.status {
+      StepStatus::Passed => attempts,
+      StepStatus::Failed | StepStatus::Ambiguous => {
+        self.counts.failures += 1;
+        format!("\n      <failure message=\"{}\" type=\"{}\">{}</failure>{}",
+                escape_xml(&failure_message(result)),
+                result.status.as_str(),
+                cdata(&self.steps.join("\n")),
+                attempts)
+      },
+      status => {
+        self.counts.skipped += 1;
+        format!("\n      <skipped message=\"{}\"/>", status.as_str())
+      },
+    };
+
+    let system_out = if self.output.is_empty() {
+      String::new()
+    } else {
+      format!("\n      <system-out>{}</system-out>", cdata(&self.output))
+    };
+
+    self.cases.push(format!("    <testcase classname=\"{}\" name=\"{}\" \
+                             time=\"{}\">{}{}\n    </testcase>",
+                            escape_xml(&feature.name),
+                            escape_xml(&scenario_name(scenario)),
+                            seconds(result.duration),
+                            outcome,
+                            system_out));
+  }
+
+  fn feature_finished(&mut self, feature: &Feature) {
+    self.suites.push(format!("  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" \
+                              errors=\"0\" skipped=\"{}\" time=\"{}\">\n{}\n  </testsuite>",
+                             escape_xml(&feature.name),
+                             self.counts.tests,
+                             self.counts.failures,
+                             self.counts.skipped,
+                             seconds(self.counts.time),
+                             self.cases.join("\n")));
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn run_finished(&mut self, _summary: &Summary) {
+    let _ = write!(self.out,
+                   "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n{}\n</testsuites>\n",
+                   self.suites.join("\n"));
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use native::test::{flaky_step, formatter_runner, run_with};
+
+  fn report() -> String {
+    run_with(&mut formatter_runner(),
+             "Feature: Reports & more\n  Scenario: Broken\n    Given I have 1\n    Then it \
+              breaks\n  Scenario: Waiting\n    Then it waits\n  Scenario Outline: Having\n    \
+              Given I have <n>\n    Examples:\n      | n |\n      | 1 |\n      | 2 |\n",
+             JUnit::new)
+  }
+
+  #[test]
+  fn it_writes_a_suite_per_feature() {
+    let report = report();
+    assert!(report.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<testsuites>\n  \
+                                <testsuite name=\"Reports &amp; more\" tests=\"4\" \
+                                failures=\"1\" errors=\"0\" skipped=\"1\""));
+    assert!(report.ends_with("  </testsuite>\n</testsuites>\n"));
+  }
+
+  #[test]
+  fn it_writes_a_case_per_scenario() {
+    let report = report();
+    assert!(report.contains("<testcase classname=\"Reports &amp; more\" name=\"Broken\""));
+    assert!(report.contains("<failure message=\"it broke\" type=\"failed\"><![CDATA[Given I \
+                             have 1"));
+    assert!(report.contains(&format!("Then it breaks{}failed\n\nit broke]]></failure>",
+                                     ".".repeat(46))));
+    assert!(report.contains("name=\"Waiting\""));
+    assert!(report.contains("<skipped message=\"pending\"/>"));
+    assert!(report.contains("name=\"Having (line 11)\""));
+    assert!(report.contains("name=\"Having (line 12)\""));
+  }
+
+  #[test]
+  fn it_reports_failed_attempts_of_retried_scenarios() {
+    let mut runner = formatter_runner().retry(1);
+    runner.given("steps.rs", 5, cucumber_regex::build("^a flaky step$"), flaky_step());
+
+    let report = run_with(&mut runner,
+                          "Feature: Retries\n  Scenario: Flaky\n    Given a flaky step\n  \
+                           Scenario: Broken\n    Then it breaks\n",
+                          JUnit::new);
+
+    assert!(report.contains("tests=\"2\" failures=\"1\""));
+    assert!(report.contains("<flakyFailure message=\"not this time\" \
+                             type=\"failed\"><![CDATA[Given a flaky step"));
+    assert!(report.contains("</failure>\n      <rerunFailure message=\"it broke\" \
+                             type=\"failed\">"));
+    assert_eq!(report.matches("<testcase").count(), 2);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn it_reports_what_steps_print() {
+    use std::fs::File;
+    use std::sync::Arc;
+
+    use tempdir::TempDir;
+
+    use native::test::{capturing, chatty_step};
+
+    let dir = TempDir::new("cucumber-junit").unwrap();
+    let file = Arc::new(File::create(dir.path().join("out.txt")).unwrap());
+    let mut runner = capturing(formatter_runner(), &file);
+    runner.given("steps.rs", 5, cucumber_regex::build("^a chatty step$"), chatty_step(file));
+
+    let report = run_with(&mut runner,
+                          "Feature: Output\n  Scenario: Chatty\n    Given a chatty step\n",
+                          JUnit::new);
+
+    assert!(report.contains("<system-out><![CDATA[Hello from a step\n]]></system-out>"));
+  }
+
+  #[test]
+  fn it_escapes_xml() {
+    assert_eq!(escape_xml("<a href=\"x\">'&'</a>"),
+               "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;");
+    assert_eq!(cdata("a]]>b"), "<![CDATA[a]]]]><![CDATA[>b]]>");
+  }
+}
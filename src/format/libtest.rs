@@ -0,0 +1,289 @@
+//! Output in the manner of Rust's built in test harness, for tools that parse
+//! what `cargo test` prints
+
+use std::io::Write;
+
+use serde_json;
+
+use format::{scenario_name, Formatter};
+use gherkin::{Feature, Scenario, Step};
+use native::{HookResult, ScenarioResult, StepResult, StepStatus, Summary};
+
+/// Reports each scenario as a test named `feature::scenario`, as libtest does
+///
+/// Either libtest's plain text is written, with the output and failure of
+/// failed tests listed at the end, or its JSON events, one per line, as
+/// written by `cargo test -- --format json`. Failed and ambiguous scenarios
+/// fail, pending and undefined ones are ignored. A retried scenario is a
+/// single test, whose output tells of its failed attempts. The scenarios a
+/// fail fast run did not get to are counted as filtered out.
+pub struct LibTest<W: Write> {
+  out: W,
+  json: bool,
+  count: usize,
+  passed: usize,
+  failed: Vec<(String, String)>,
+  ignored: usize,
+  // What the current scenario printed, followed by its failure
+  stdout: String,
+  // Whether the current scenario is being retried, so it is still the same test
+  retrying: bool,
+}
+
+impl<W: Write> LibTest<W> {
+  /// Write libtest's plain text output
+  pub fn new(out: W) -> LibTest<W> {
+    LibTest {
+      out: out,
+      json: false,
+      count: 0,
+      passed: 0,
+      failed: Vec::new(),
+      ignored: 0,
+      stdout: String::new(),
+      retrying: false,
+    }
+  }
+
+  /// Write libtest's JSON events
+  pub fn json(out: W) -> LibTest<W> {
+    LibTest { json: true, ..LibTest::new(out) }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn line(&mut self, text: &str) {
+    let _ = writeln!(self.out, "{}", text);
+  }
+}
+
+fn quote(text: &str) -> String {
+  serde_json::to_string(&text).unwrap()
+}
+
+fn test_name(feature: &Feature, scenario: &Scenario) -> String {
+  format!("{}::{}", feature.name, scenario_name(scenario))
+}
+
+impl<W: Write> Formatter for LibTest<W> {
+  fn run_started(&mut self, features: &[Feature]) {
+    let count = features.iter().map(|feature| feature.scenarios.len()).sum::<usize>();
+    self.count = count;
+    if self.json {
+      self.line(&format!("{{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": {} }}",
+                         count));
+    } else {
+      self.line("");
+      self.line(&format!("running {} test{}", count, if count == 1 { "" } else { "s" }));
+    }
+  }
+
+  fn scenario_started(&mut self, feature: &Feature, scenario: &Scenario) {
+    if self.retrying {
+      return;
+    }
+    self.stdout.clear();
+    if self.json {
+      let name = quote(&test_name(feature, scenario));
+      self.line(&format!("{{ \"type\": \"test\", \"event\": \"started\", \"name\": {} }}", name));
+    }
+  }
+
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    if let Some(failure) = result.failure() {
+      self.stdout.push_str(&format!("{} hook failed: {}\n{}\n",
+                                    result.kind.as_str(),
+                                    result.source,
+                                    failure.message()));
+    }
+  }
+
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    self.stdout.push_str(&result.output);
+    if let Some(failure) = result.failure() {
+      self.stdout.push_str(&format!("Step failed: {} {}\n{}\n",
+                                    step.keyword,
+                                    step.text,
+                                    failure.message()));
+    }
+  }
+
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    self.retrying = result.retried;
+    if result.retried {
+      self.stdout.push_str(&format!("Attempt {} failed, retrying\n", result.attempt));
+      return;
+    }
+
+    let name = test_name(feature, scenario);
+    let event = match result.status {
+      StepStatus::Passed => {
+        self.passed += 1;
+        "ok"
+      },
+      StepStatus::Failed | StepStatus::Ambiguous => {
+        self.failed.push((name.clone(), self.stdout.clone()));
+        "failed"
+      },
+      _ => {
+        self.ignored += 1;
+        "ignored"
+      },
+    };
+
+    if self.json {
+      let stdout = if event == "failed" {
+        format!(", \"stdout\": {}", quote(&self.stdout))
+      } else {
+        String::new()
+      };
+      self.line(&format!("{{ \"type\": \"test\", \"name\": {}, \"event\": \"{}\"{} }}",
+                         quote(&name),
+                         event,
+                         stdout));
+    } else {
+      let outcome = if event == "failed" { "FAILED" } else { event };
+      self.line(&format!("test {} ... {}", name, outcome));
+    }
+  }
+
+  fn run_finished(&mut self, summary: &Summary) {
+    let outcome = if self.failed.is_empty() { "ok" } else { "failed" };
+    let seconds = summary.duration.as_secs() as f64 +
+                  summary.duration.subsec_nanos() as f64 / 1000000000.0;
+    let not_run = self.count - self.passed - self.failed.len() - self.ignored;
+
+    if self.json {
+      let line = format!("{{ \"type\": \"suite\", \"event\": \"{}\", \"passed\": {}, \"failed\": \
+                          {}, \"ignored\": {}, \"measured\": 0, \"filtered_out\": {}, \
+                          \"exec_time\": {} }}",
+                         outcome,
+                         self.passed,
+                         self.failed.len(),
+                         self.ignored,
+                         not_run,
+                         seconds);
+      self.line(&line);
+    } else {
+      if !self.failed.is_empty() {
+        let mut failures = "\nfailures:\n".to_owned();
+        for &(ref name, ref stdout) in &self.failed {
+          failures.push_str(&format!("\n---- {} stdout ----\n{}", name, stdout));
+        }
+        failures.push_str("\nfailures:\n");
+        for &(ref name, _) in &self.failed {
+          failures.push_str(&format!("    {}\n", name));
+        }
+        let _ = write!(self.out, "{}", failures);
+      }
+
+      self.line("");
+      let line = format!("test result: {}. {} passed; {} failed; {} ignored; 0 measured; {} \
+                          filtered out; finished in {:.2}s",
+                         if self.failed.is_empty() { "ok" } else { "FAILED" },
+                         self.passed,
+                         self.failed.len(),
+                         self.ignored,
+                         not_run,
+                         seconds);
+      self.line(&line);
+      self.line("");
+    }
+
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use gherkin;
+  use native::test::{formatter_runner, run_with};
+
+  fn run(json: bool) -> String {
+    let output = run_with(&mut formatter_runner(),
+                          "Feature: Tests\n  Scenario: Working\n    Given it works\n  Scenario: \
+                           Broken\n    Then it breaks\n  Scenario: Missing\n    Then it is \
+                           missing\n",
+                          |out| if json { LibTest::json(out) } else { LibTest::new(out) });
+    // Durations vary, so they are left out of comparisons
+    output.lines()
+      .map(|line| match line.find("finished in").or(line.find("\"exec_time\"")) {
+        Some(index) => line[..index].to_owned(),
+        None => line.to_owned(),
+      })
+      .collect::<Vec<_>>()
+      .join("\n")
+  }
+  #[test]
+  fn it_writes_libtest_text() {
+    assert_eq!(run(false),
+               "\nrunning 3 tests\ntest Tests::Working ... ok\ntest Tests::Broken ... \
+                FAILED\ntest Tests::Missing ... ignored\n\nfailures:\n\n---- Tests::Broken \
+                stdout ----\nStep failed: Then it breaks\nit broke\n\nfailures:\n    \
+                Tests::Broken\n\ntest result: FAILED. 1 passed; 1 failed; 1 ignored; 0 \
+                measured; 0 filtered out; \n");
+  }
+
+  #[test]
+  fn it_writes_libtest_json() {
+    let output = run(true);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(lines,
+               vec!["{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 3 }",
+                    "{ \"type\": \"test\", \"event\": \"started\", \"name\": \"Tests::Working\" }",
+                    "{ \"type\": \"test\", \"name\": \"Tests::Working\", \"event\": \"ok\" }",
+                    "{ \"type\": \"test\", \"event\": \"started\", \"name\": \"Tests::Broken\" }",
+                    "{ \"type\": \"test\", \"name\": \"Tests::Broken\", \"event\": \"failed\", \
+                     \"stdout\": \"Step failed: Then it breaks\\nit broke\\n\" }",
+                    "{ \"type\": \"test\", \"event\": \"started\", \"name\": \"Tests::Missing\" }",
+                    "{ \"type\": \"test\", \"name\": \"Tests::Missing\", \"event\": \"ignored\" }",
+                    "{ \"type\": \"suite\", \"event\": \"failed\", \"passed\": 1, \"failed\": \
+                     1, \"ignored\": 1, \"measured\": 0, \"filtered_out\": 0, "]);
+  }
+
+  #[test]
+  fn it_reports_retried_scenarios_as_one_test() {
+    let output = run_with(&mut formatter_runner().retry(1),
+                          "Feature: Tests\n  Scenario: Broken\n    Then it breaks\n",
+                          LibTest::json);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(&lines[1..3],
+               &["{ \"type\": \"test\", \"event\": \"started\", \"name\": \"Tests::Broken\" }",
+                 "{ \"type\": \"test\", \"name\": \"Tests::Broken\", \"event\": \"failed\", \
+                  \"stdout\": \"Step failed: Then it breaks\\nit broke\\nAttempt 1 failed, \
+                  retrying\\nStep failed: Then it breaks\\nit broke\\n\" }"]);
+  }
+
+  #[test]
+  fn it_counts_scenarios_not_run_as_filtered_out() {
+    let output = run_with(&mut formatter_runner().fail_fast(true),
+                          "Feature: Tests\n  Scenario: Broken\n    Then it breaks\n  Scenario: \
+                           Unrun\n    Then it breaks\n",
+                          LibTest::new);
+    assert!(output.contains("\nrunning 2 tests\n"));
+    assert!(output.contains("test result: FAILED. 0 passed; 1 failed; 0 ignored; 0 measured; 1 \
+                             filtered out;"));
+  }
+
+  #[test]
+  fn it_names_outline_rows_by_line() {
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: F\n  Scenario Outline: O\n    Given <x>\n    \
+                                  Examples:\n      | x |\n      | a |\n")
+      .unwrap();
+    assert_eq!(test_name(&feature, &feature.scenarios[0]), "F::O (line 6)");
+  }
+}
@@ -0,0 +1,786 @@
+//! [Cucumber Messages](https://github.com/cucumber/messages), as read by the
+//! official HTML formatter and other message based tools
+
+#[cfg(feature = "serde_macros")]
+include!("messages.rs.in");
+
+#[cfg(not(feature = "serde_macros"))]
+include!(concat!(env!("OUT_DIR"), "/format/messages.rs"));
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::time::{self, SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use definitions::listing::StepDefinition as Definition;
+use format::Formatter;
+use definitions::registration::HookKind;
+use gherkin::{self, Feature, Scenario, StepArgument};
+use native::{HookResult, ScenarioResult, StepResult, StepStatus, Summary};
+
+/// The version of the messages protocol written
+pub const PROTOCOL_VERSION: &'static str = "21.0.1";
+
+/// Writes a stream of Cucumber Messages as newline delimited JSON
+///
+/// The step definitions of the run are given up front, and are reported
+/// before anything runs. Each feature's source, its Gherkin document and a
+/// pickle for each of its scenarios are reported as it starts, and each
+/// scenario's test case and step results as it finishes. Hooks are reported
+/// the first time they run, and are test steps of the test cases they ran
+/// in, around the steps of the pickle.
+///
+/// Scenario outlines are reported as one scenario per row of their examples,
+/// as the native runner has them. Every attempt of a retried scenario is
+/// reported as a start of the same test case.
+pub struct Messages<W: Write> {
+  out: W,
+  run_started: bool,
+  next_id: usize,
+  // Message ids of the step definitions, by their id in the registry
+  definition_ids: HashMap<String, String>,
+  definitions: Vec<StepDefinition>,
+  // Message ids of the hooks reported so far, by where they were defined
+  hook_ids: HashMap<String, String>,
+  // Pickles of the current feature, by the line of their scenario
+  pickles: HashMap<usize, PickleIds>,
+  // When the current scenario started, and when each of its hooks and
+  // steps finished
+  scenario_start: Timestamp,
+  hook_ends: Vec<Timestamp>,
+  step_ends: Vec<Timestamp>,
+  // The test case of the scenario being retried, for its next attempt
+  retried_case: Option<TestCase>,
+}
+
+struct PickleIds {
+  pickle: String,
+  steps: Vec<String>,
+}
+
+impl<W: Write> Messages<W> {
+  pub fn new<I>(out: W, definitions: I) -> Messages<W>
+    where I: IntoIterator<Item = Definition>
+  {
+    let mut messages = Messages {
+      out: out,
+      run_started: false,
+      next_id: 0,
+      definition_ids: HashMap::new(),
+      definitions: Vec::new(),
+      hook_ids: HashMap::new(),
+      pickles: HashMap::new(),
+      scenario_start: now(),
+      hook_ends: Vec::new(),
+      step_ends: Vec::new(),
+      retried_case: None,
+    };
+
+    for definition in definitions {
+      let id = messages.id();
+      messages.definition_ids.insert(definition.id.to_string(), id.clone());
+      messages.definitions.push(step_definition(id, definition));
+    }
+
+    messages
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  fn id(&mut self) -> String {
+    self.next_id += 1;
+    (self.next_id - 1).to_string()
+  }
+
+  // The run starts with the first feature, or finishes without any
+  fn start_run(&mut self) {
+    if self.run_started {
+      return;
+    }
+    self.run_started = true;
+
+    let meta = Meta {
+      protocol_version: PROTOCOL_VERSION.to_owned(),
+      implementation: Product {
+        name: "cucumber-rust".to_owned(),
+        version: Some(env!("CARGO_PKG_VERSION").to_owned()),
+      },
+      runtime: Product {
+        name: "rust".to_owned(),
+        version: None,
+      },
+      os: Product {
+        name: env::consts::OS.to_owned(),
+        version: None,
+      },
+      cpu: Product {
+        name: env::consts::ARCH.to_owned(),
+        version: None,
+      },
+    };
+    self.emit(Envelope { meta: Some(meta), ..Envelope::default() });
+    for definition in self.definitions.clone() {
+      self.emit(Envelope { step_definition: Some(definition), ..Envelope::default() });
+    }
+    let started = TestRunStarted { timestamp: now() };
+    self.emit(Envelope { test_run_started: Some(started), ..Envelope::default() });
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn emit(&mut self, envelope: Envelope) {
+    let _ = writeln!(self.out, "{}", serde_json::to_string(&envelope).unwrap());
+  }
+
+  fn tags(&mut self, names: &[String], lines: &[usize]) -> Vec<Tag> {
+    names.iter()
+      .zip(lines)
+      .map(|(name, &line)| {
+        Tag {
+          location: Location { line: line },
+          name: name.clone(),
+          id: self.id(),
+        }
+      })
+      .collect()
+  }
+
+  // The test case of a pickle, with the hooks that ran around it and the
+  // step definitions its steps matched
+  fn test_case(&mut self,
+               pickle_id: String,
+               pickle_step_ids: Vec<String>,
+               result: &ScenarioResult)
+               -> TestCase {
+    let before = self.hook_steps(result, HookKind::Before);
+    let steps = result.steps
+      .iter()
+      .zip(pickle_step_ids)
+      .map(|(step, pickle_step_id)| {
+        let (definition_ids, arguments) = match step.matched {
+          Some(ref matched) => {
+            let arguments = matched.args
+              .iter()
+              .map(|arg| {
+                StepMatchArgument {
+                  group: Group {
+                    start: arg.pos,
+                    value: arg.val.clone(),
+                    children: Vec::new(),
+                  },
+                }
+              })
+              .collect();
+            let ids = self.definition_ids.get(&matched.id).cloned().into_iter().collect();
+            (ids, vec![StepMatchArgumentsList { step_match_arguments: arguments }])
+          },
+          None => (Vec::new(), Vec::new()),
+        };
+        TestStep {
+          id: self.id(),
+          hook_id: None,
+          pickle_step_id: Some(pickle_step_id),
+          step_definition_ids: Some(definition_ids),
+          step_match_arguments_lists: Some(arguments),
+        }
+      })
+      .collect::<Vec<_>>();
+    let after = self.hook_steps(result, HookKind::After);
+
+    TestCase {
+      id: self.id(),
+      pickle_id: pickle_id,
+      test_steps: before.into_iter().chain(steps).chain(after).collect(),
+    }
+  }
+
+  fn hook_steps(&mut self, result: &ScenarioResult, kind: HookKind) -> Vec<TestStep> {
+    result.hooks
+      .iter()
+      .filter(|hook| hook.kind == kind)
+      .map(|hook| {
+        TestStep {
+          id: self.id(),
+          hook_id: self.hook_ids.get(&hook.source).cloned(),
+          pickle_step_id: None,
+          step_definition_ids: None,
+          step_match_arguments_lists: None,
+        }
+      })
+      .collect()
+  }
+
+  fn steps(&mut self, steps: &[gherkin::Step]) -> Vec<GherkinStep> {
+    steps.iter()
+      .map(|step| {
+        let (doc_string, data_table) = match step.argument {
+          Some(StepArgument::DocString(ref content)) => {
+            let doc_string = DocString {
+              location: Location { line: step.line + 1 },
+              content: content.clone(),
+              delimiter: "\"\"\"".to_owned(),
+            };
+            (Some(doc_string), None)
+          },
+          Some(StepArgument::Table(ref rows)) => {
+            let table = DataTable {
+              location: Location { line: step.line + 1 },
+              rows: rows.iter()
+                .enumerate()
+                .map(|(index, row)| {
+                  TableRow {
+                    id: self.id(),
+                    location: Location { line: step.line + 1 + index },
+                    cells: row.iter()
+                      .map(|value| {
+                        TableCell {
+                          location: Location { line: step.line + 1 + index },
+                          value: value.clone(),
+                        }
+                      })
+                      .collect(),
+                  }
+                })
+                .collect(),
+            };
+            (None, Some(table))
+          },
+          None => (None, None),
+        };
+
+        GherkinStep {
+          id: self.id(),
+          location: Location { line: step.line },
+          keyword: format!("{} ", step.keyword),
+          text: step.text.clone(),
+          doc_string: doc_string,
+          data_table: data_table,
+        }
+      })
+      .collect()
+  }
+}
+
+fn now() -> Timestamp {
+  timestamp(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or(time::Duration::new(0, 0)))
+}
+
+fn timestamp(duration: time::Duration) -> Timestamp {
+  Timestamp {
+    seconds: duration.as_secs(),
+    nanos: duration.subsec_nanos(),
+  }
+}
+
+// The time a step of the given duration started, when it finished at `end`
+fn started(end: Timestamp, duration: time::Duration) -> Timestamp {
+  let end = time::Duration::new(end.seconds, end.nanos);
+  timestamp(if end > duration { end - duration } else { end })
+}
+
+fn step_definition(id: String, definition: Definition) -> StepDefinition {
+  StepDefinition {
+    id: id,
+    pattern: StepDefinitionPattern {
+      source: definition.pattern,
+      kind: "REGULAR_EXPRESSION".to_owned(),
+    },
+    source_reference: source_reference(&definition.source),
+  }
+}
+
+// Sources of steps and hooks are written as `file:line`
+fn source_reference(source: &str) -> SourceReference {
+  let (uri, line) = match source.rfind(':') {
+    Some(index) => (source[..index].to_owned(), source[index + 1..].parse().unwrap_or(0)),
+    None => (source.to_owned(), 0),
+  };
+
+  SourceReference {
+    uri: uri,
+    location: Location { line: line },
+  }
+}
+
+fn pickle_argument(argument: &StepArgument) -> PickleStepArgument {
+  match argument {
+    &StepArgument::DocString(ref content) => {
+      PickleStepArgument {
+        doc_string: Some(PickleDocString { content: content.clone() }),
+        data_table: None,
+      }
+    },
+    &StepArgument::Table(ref rows) => {
+      let rows = rows.iter()
+        .map(|row| {
+          PickleTableRow {
+            cells: row.iter().map(|value| PickleTableCell { value: value.clone() }).collect(),
+          }
+        })
+        .collect();
+      PickleStepArgument {
+        doc_string: None,
+        data_table: Some(PickleTable { rows: rows }),
+      }
+    },
+  }
+}
+
+fn read_source(path: &str) -> Option<String> {
+  let mut source = String::new();
+  File::open(path).and_then(|mut file| file.read_to_string(&mut source)).ok().map(|_| source)
+}
+
+impl<W: Write> Formatter for Messages<W> {
+  fn feature_started(&mut self, feature: &Feature) {
+    self.start_run();
+
+    if let Some(data) = read_source(&feature.path) {
+      let source = Source {
+        uri: feature.path.clone(),
+        data: data,
+        media_type: "text/x.cucumber.gherkin+plain".to_owned(),
+      };
+      self.emit(Envelope { source: Some(source), ..Envelope::default() });
+    }
+
+    let feature_tags = self.tags(&feature.tags, &feature.tag_lines);
+    let mut children = Vec::new();
+
+    let background_steps = self.steps(&feature.background);
+    let background_step_ids: Vec<String> =
+      background_steps.iter().map(|step| step.id.clone()).collect();
+    if let Some(line) = feature.background_line {
+      children.push(FeatureChild {
+        background: Some(Background {
+          id: self.id(),
+          location: Location { line: line },
+          keyword: "Background".to_owned(),
+          name: String::new(),
+          description: String::new(),
+          steps: background_steps,
+        }),
+        scenario: None,
+      });
+    }
+
+    let mut pickles = Vec::new();
+    self.pickles.clear();
+    for scenario in &feature.scenarios {
+      // NOTE: Rules and examples are flattened into scenarios, so their tags
+      // are reported as the scenario's
+      let names = scenario.rule_tags
+        .iter()
+        .chain(scenario.tags.iter())
+        .chain(scenario.examples_tags.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+      let lines = scenario.rule_tag_lines
+        .iter()
+        .chain(scenario.tag_lines.iter())
+        .chain(scenario.examples_tag_lines.iter())
+        .cloned()
+        .collect::<Vec<_>>();
+      let tags = self.tags(&names, &lines);
+      // NOTE: Likewise, the steps of a rule's background are reported as the
+      // scenario's first steps
+      let scenario_steps: Vec<gherkin::Step> =
+        scenario.rule_background.iter().chain(scenario.steps.iter()).cloned().collect();
+      let steps = self.steps(&scenario_steps);
+      let gherkin_scenario = GherkinScenario {
+        id: self.id(),
+        location: Location { line: scenario.line },
+        tags: tags,
+        keyword: scenario.keyword.clone(),
+        name: scenario.name.clone(),
+        description: String::new(),
+        steps: steps,
+        examples: Vec::new(),
+      };
+
+      let ast_step_ids = background_step_ids.iter()
+        .chain(gherkin_scenario.steps.iter().map(|step| &step.id))
+        .cloned()
+        .collect::<Vec<_>>();
+      // And, But and * continue the kind of step before them
+      let mut kind = "Context";
+      let steps = feature.background
+        .iter()
+        .chain(scenario_steps.iter())
+        .zip(ast_step_ids)
+        .map(|(step, ast_id)| {
+          kind = match step.keyword.as_str() {
+            "Given" => "Context",
+            "When" => "Action",
+            "Then" => "Outcome",
+            _ => kind,
+          };
+          PickleStep {
+            id: self.id(),
+            text: step.text.clone(),
+            kind: kind.to_owned(),
+            argument: step.argument.as_ref().map(pickle_argument),
+            ast_node_ids: vec![ast_id],
+          }
+        })
+        .collect::<Vec<_>>();
+
+      let tags = feature_tags.iter()
+        .chain(gherkin_scenario.tags.iter())
+        .map(|tag| {
+          PickleTag {
+            name: tag.name.clone(),
+            ast_node_id: tag.id.clone(),
+          }
+        })
+        .collect();
+
+      let pickle = Pickle {
+        id: self.id(),
+        uri: feature.path.clone(),
+        name: scenario.name.clone(),
+        language: "en".to_owned(),
+        steps: steps,
+        tags: tags,
+        ast_node_ids: vec![gherkin_scenario.id.clone()],
+      };
+      self.pickles.insert(scenario.line,
+                          PickleIds {
+                            pickle: pickle.id.clone(),
+                            steps: pickle.steps.iter().map(|step| step.id.clone()).collect(),
+                          });
+      pickles.push(pickle);
+      children.push(FeatureChild {
+        background: None,
+        scenario: Some(gherkin_scenario),
+      });
+    }
+
+    let document = GherkinDocument {
+      uri: feature.path.clone(),
+      feature: GherkinFeature {
+        location: Location { line: feature.line },
+        tags: feature_tags,
+        language: "en".to_owned(),
+        keyword: "Feature".to_owned(),
+        name: feature.name.clone(),
+        description: feature.description.clone(),
+        children: children,
+      },
+      comments: Vec::new(),
+    };
+    self.emit(Envelope { gherkin_document: Some(document), ..Envelope::default() });
+    for pickle in pickles {
+      self.emit(Envelope { pickle: Some(pickle), ..Envelope::default() });
+    }
+  }
+
+  fn scenario_started(&mut self, _feature: &Feature, _scenario: &Scenario) {
+    self.scenario_start = now();
+    self.hook_ends.clear();
+    self.step_ends.clear();
+  }
+
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    self.hook_ends.push(now());
+    if self.hook_ids.contains_key(&result.source) {
+      return;
+    }
+    let hook = Hook {
+      id: self.id(),
+      source_reference: source_reference(&result.source),
+    };
+    self.hook_ids.insert(result.source.clone(), hook.id.clone());
+    self.emit(Envelope { hook: Some(hook), ..Envelope::default() });
+  }
+
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   _step: &gherkin::Step,
+                   _result: &StepResult) {
+    self.step_ends.push(now());
+  }
+
+  // NOTE: Test cases are only reported once they have run, as which step
+  // definitions their steps match is only known then
+  fn scenario_finished(&mut self,
+                       _feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    let (pickle_id, pickle_step_ids) = match self.pickles.get(&scenario.line) {
+      Some(ids) => (ids.pickle.clone(), ids.steps.clone()),
+      None => return,
+    };
+
+    let test_case = match self.retried_case.take() {
+      Some(test_case) => test_case,
+      None => {
+        let test_case = self.test_case(pickle_id, pickle_step_ids, result);
+        self.emit(Envelope { test_case: Some(test_case.clone()), ..Envelope::default() });
+        test_case
+      },
+    };
+    let test_case_started = TestCaseStarted {
+      id: self.id(),
+      test_case_id: test_case.id.clone(),
+      attempt: result.attempt - 1,
+      timestamp: self.scenario_start,
+    };
+    let started_id = test_case_started.id.clone();
+    self.emit(Envelope { test_case_started: Some(test_case_started), ..Envelope::default() });
+
+    // The status, duration and message of each test step, with when it
+    // finished, in the order of the test case
+    let hook_ran = |hook: &HookResult, &end: &Timestamp| {
+      let message = hook.failure().map(|failure| failure.message().to_owned());
+      (hook.status, hook.duration, message, end)
+    };
+    let hooks = result.hooks.iter().zip(&self.hook_ends);
+    let before = hooks.clone().filter(|&(hook, _)| hook.kind == HookKind::Before);
+    let after = hooks.filter(|&(hook, _)| hook.kind == HookKind::After);
+    let steps = result.steps.iter().zip(&self.step_ends).map(|(step, &end)| {
+      let message = match (step.failure(), step.pending_message()) {
+        (Some(failure), _) => Some(failure.message().to_owned()),
+        (None, Some(pending)) => Some(pending.to_owned()),
+        (None, None) => None,
+      };
+      (step.status, step.duration, message, end)
+    });
+    let ran: Vec<(StepStatus, time::Duration, Option<String>, Timestamp)> =
+      before.map(|(hook, end)| hook_ran(hook, end))
+        .chain(steps)
+        .chain(after.map(|(hook, end)| hook_ran(hook, end)))
+        .collect();
+
+    for (test_step, (status, duration, message, end)) in test_case.test_steps.iter().zip(ran) {
+      let step_started = TestStepStarted {
+        test_case_started_id: started_id.clone(),
+        test_step_id: test_step.id.clone(),
+        timestamp: started(end, duration),
+      };
+      let step_finished = TestStepFinished {
+        test_case_started_id: started_id.clone(),
+        test_step_id: test_step.id.clone(),
+        test_step_result: TestStepResult {
+          status: status.as_str().to_uppercase(),
+          duration: timestamp(duration),
+          message: message,
+        },
+        timestamp: end,
+      };
+      self.emit(Envelope { test_step_started: Some(step_started), ..Envelope::default() });
+      self.emit(Envelope { test_step_finished: Some(step_finished), ..Envelope::default() });
+    }
+
+    let finished = TestCaseFinished {
+      test_case_started_id: started_id,
+      timestamp: now(),
+      will_be_retried: result.retried,
+    };
+    self.emit(Envelope { test_case_finished: Some(finished), ..Envelope::default() });
+
+    if result.retried {
+      self.retried_case = Some(test_case);
+    }
+  }
+
+  fn run_finished(&mut self, summary: &Summary) {
+    self.start_run();
+    let finished = TestRunFinished {
+      success: summary.passed(),
+      timestamp: now(),
+    };
+    self.emit(Envelope { test_run_finished: Some(finished), ..Envelope::default() });
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use serde_json::{self, Value};
+
+  use definitions::registration::CucumberRegistrar;
+  use native::NativeRunner;
+  use native::test::{formatter_runner, run_with};
+
+  fn messages() -> Vec<Value> {
+    run(&mut formatter_runner())
+  }
+
+  fn run(runner: &mut NativeRunner<u32>) -> Vec<Value> {
+    let definitions: Vec<Definition> = runner.cucumber().definitions().collect();
+    run_with(runner,
+             "@messages\nFeature: Messages\n  Background:\n    Given I have 1\n  Scenario: \
+              Breaking\n    Given I have 2\n    Then it breaks\n    And I have 3\n",
+             |out| Messages::new(out, definitions))
+      .lines()
+      .map(|line| serde_json::from_str(line).unwrap())
+      .collect()
+  }
+
+  fn kind(message: &Value) -> String {
+    message.as_object().unwrap().keys().next().unwrap().clone()
+  }
+
+  fn string(message: &Value, pointer: &str) -> String {
+    message.pointer(pointer).and_then(Value::as_string).unwrap().to_owned()
+  }
+
+  #[test]
+  fn it_writes_messages_in_order() {
+    let kinds: Vec<String> = messages().iter().map(kind).collect();
+    assert_eq!(kinds,
+               vec!["meta",
+                    "stepDefinition",
+                    "stepDefinition",
+                    "stepDefinition",
+                    "stepDefinition",
+                    "testRunStarted",
+                    "gherkinDocument",
+                    "pickle",
+                    "testCase",
+                    "testCaseStarted",
+                    "testStepStarted",
+                    "testStepFinished",
+                    "testStepStarted",
+                    "testStepFinished",
+                    "testStepStarted",
+                    "testStepFinished",
+                    "testStepStarted",
+                    "testStepFinished",
+                    "testCaseFinished",
+                    "testRunFinished"]);
+  }
+
+  #[test]
+  fn it_describes_step_definitions() {
+    let messages = messages();
+    assert_eq!(string(&messages[1], "/stepDefinition/pattern/source"), "^I have (\\d+)$");
+    assert_eq!(string(&messages[1], "/stepDefinition/pattern/type"), "REGULAR_EXPRESSION");
+    assert_eq!(string(&messages[3], "/stepDefinition/sourceReference/uri"), "steps.rs");
+    assert_eq!(messages[3].pointer("/stepDefinition/sourceReference/location/line"),
+               Some(&Value::U64(3)));
+  }
+
+  #[test]
+  fn it_links_pickles_to_their_gherkin() {
+    let messages = messages();
+    let document = &messages[6];
+    let pickle = &messages[7];
+
+    assert_eq!(string(pickle, "/pickle/astNodeIds/0"),
+               string(document, "/gherkinDocument/feature/children/1/scenario/id"));
+    assert_eq!(string(pickle, "/pickle/steps/0/astNodeIds/0"),
+               string(document,
+                      "/gherkinDocument/feature/children/0/background/steps/0/id"));
+    assert_eq!(string(pickle, "/pickle/steps/0/type"), "Context");
+    assert_eq!(string(pickle, "/pickle/steps/3/type"), "Outcome");
+    assert_eq!(string(pickle, "/pickle/tags/0/name"), "@messages");
+    assert_eq!(string(pickle, "/pickle/tags/0/astNodeId"),
+               string(document, "/gherkinDocument/feature/tags/0/id"));
+  }
+
+  #[test]
+  fn it_locates_tags_on_their_own_lines() {
+    let mut runner = formatter_runner();
+    let definitions: Vec<Definition> = runner.cucumber().definitions().collect();
+    let output = run_with(&mut runner,
+                          "Feature: Tags\n  @slow\n  @db # Needs a database\n\n  Scenario: \
+                           Tagged\n    Given it works\n",
+                          |out| Messages::new(out, definitions));
+    let document = output.lines()
+      .map(|line| serde_json::from_str::<Value>(line).unwrap())
+      .find(|message| kind(message) == "gherkinDocument")
+      .unwrap();
+
+    let tags = "/gherkinDocument/feature/children/0/scenario/tags";
+    assert_eq!(document.pointer(&format!("{}/0/location/line", tags)), Some(&Value::U64(2)));
+    assert_eq!(document.pointer(&format!("{}/1/location/line", tags)), Some(&Value::U64(3)));
+  }
+
+  #[test]
+  fn it_reports_test_cases() {
+    let messages = messages();
+    let test_case = &messages[8];
+
+    assert_eq!(string(test_case, "/testCase/pickleId"), string(&messages[7], "/pickle/id"));
+    assert_eq!(string(test_case, "/testCase/testSteps/1/stepDefinitionIds/0"),
+               string(&messages[1], "/stepDefinition/id"));
+    assert_eq!(string(test_case,
+                      "/testCase/testSteps/1/stepMatchArgumentsLists/0/stepMatchArguments/0/\
+                       group/value"),
+               "2");
+    assert_eq!(string(&messages[9], "/testCaseStarted/testCaseId"),
+               string(test_case, "/testCase/id"));
+    assert_eq!(string(&messages[13], "/testStepFinished/testStepId"),
+               string(test_case, "/testCase/testSteps/1/id"));
+    assert_eq!(string(&messages[15], "/testStepFinished/testStepResult/status"), "FAILED");
+    assert_eq!(string(&messages[15], "/testStepFinished/testStepResult/message"),
+               "it broke");
+    assert_eq!(string(&messages[17], "/testStepFinished/testStepResult/status"), "SKIPPED");
+    assert_eq!(messages[19].pointer("/testRunFinished/success"), Some(&Value::Bool(false)));
+  }
+
+  #[test]
+  fn it_reports_attempts_of_one_test_case() {
+    let messages = run(&mut formatter_runner().retry(1));
+    let of_kind = |wanted: &str| {
+      messages.iter().filter(|message| kind(message) == wanted).collect::<Vec<_>>()
+    };
+
+    assert_eq!(of_kind("testCase").len(), 1);
+    let started = of_kind("testCaseStarted");
+    assert_eq!(started.iter()
+                 .map(|message| message.pointer("/testCaseStarted/attempt").unwrap().clone())
+                 .collect::<Vec<_>>(),
+               vec![Value::U64(0), Value::U64(1)]);
+    assert_eq!(string(started[1], "/testCaseStarted/testCaseId"),
+               string(of_kind("testCase")[0], "/testCase/id"));
+    assert_eq!(of_kind("testCaseFinished")
+                 .iter()
+                 .map(|message| message.pointer("/testCaseFinished/willBeRetried").unwrap().clone())
+                 .collect::<Vec<_>>(),
+               vec![Value::Bool(true), Value::Bool(false)]);
+  }
+
+  #[test]
+  fn it_reports_hooks_as_test_steps() {
+    let mut runner = formatter_runner();
+    runner.before("hooks.rs", 3, None, Box::new(|c, _| c.fail("no database")));
+    runner.after("hooks.rs", 9, None, Box::new(|_, _| ()));
+    let messages = run(&mut runner);
+    let of_kind = |wanted: &str| {
+      messages.iter().filter(|message| kind(message) == wanted).collect::<Vec<_>>()
+    };
+
+    let hooks = of_kind("hook");
+    assert_eq!(hooks.len(), 2);
+    assert_eq!(string(hooks[0], "/hook/sourceReference/uri"), "hooks.rs");
+    assert_eq!(hooks[0].pointer("/hook/sourceReference/location/line"), Some(&Value::U64(3)));
+
+    let test_case = of_kind("testCase")[0];
+    assert_eq!(test_case.pointer("/testCase/testSteps").and_then(Value::as_array).unwrap().len(),
+               6);
+    assert_eq!(string(test_case, "/testCase/testSteps/0/hookId"), string(hooks[0], "/hook/id"));
+    assert_eq!(test_case.pointer("/testCase/testSteps/0/pickleStepId"), None);
+    assert_eq!(string(test_case, "/testCase/testSteps/5/hookId"), string(hooks[1], "/hook/id"));
+
+    let finished = of_kind("testStepFinished");
+    let statuses: Vec<String> = finished.iter()
+      .map(|message| string(message, "/testStepFinished/testStepResult/status"))
+      .collect();
+    assert_eq!(statuses, vec!["FAILED", "SKIPPED", "SKIPPED", "SKIPPED", "SKIPPED", "PASSED"]);
+    assert_eq!(string(finished[0], "/testStepFinished/testStepId"),
+               string(test_case, "/testCase/testSteps/0/id"));
+    assert_eq!(string(finished[0], "/testStepFinished/testStepResult/message"),
+               "no database");
+  }
+}
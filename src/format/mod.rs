@@ -0,0 +1,187 @@
+//! Reporting of native runs
+//!
+//! A [NativeRunner](../native/struct.NativeRunner.html) tells a
+//! [Formatter](./trait.Formatter.html) about features, scenarios and steps as
+//! it runs them.
+
+use gherkin::{Feature, Scenario, Step};
+use event::response::Step as MatchedStep;
+use native::{HookResult, ScenarioResult, StepResult, Summary};
+
+pub mod pretty;
+pub mod junit;
+pub mod json;
+pub mod messages;
+pub mod tap;
+pub mod libtest;
+pub mod rerun;
+
+/// The built in formatters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+  /// [Pretty](./pretty/struct.Pretty.html) console output
+  Pretty,
+  /// A [JUnit](./junit/struct.JUnit.html) XML report
+  JUnit,
+  /// A [Cucumber JSON](./json/struct.Json.html) report
+  Json,
+  /// A stream of [Cucumber Messages](./messages/struct.Messages.html)
+  Messages,
+  /// [TAP](./tap/struct.Tap.html) test points
+  Tap,
+  /// [libtest](./libtest/struct.LibTest.html) style text
+  LibTest,
+  /// [libtest](./libtest/struct.LibTest.html) style JSON events
+  LibTestJson,
+  /// The [failed scenarios](./rerun/struct.Rerun.html), to run them again
+  Rerun,
+}
+
+impl Format {
+  /// Look up a formatter by the name given to `--format`
+  pub fn from_name(name: &str) -> Option<Format> {
+    match name {
+      "pretty" => Some(Format::Pretty),
+      "junit" => Some(Format::JUnit),
+      "json" => Some(Format::Json),
+      "message" => Some(Format::Messages),
+      "tap" => Some(Format::Tap),
+      "libtest" => Some(Format::LibTest),
+      "libtest-json" => Some(Format::LibTestJson),
+      "rerun" => Some(Format::Rerun),
+      _ => None,
+    }
+  }
+
+  /// Whether the formatter reports what steps print, which then needs to be
+  /// captured
+  pub fn reports_output(&self) -> bool {
+    match self {
+      &Format::JUnit | &Format::Json | &Format::LibTest | &Format::LibTestJson => true,
+      &Format::Pretty | &Format::Messages | &Format::Tap | &Format::Rerun => false,
+    }
+  }
+}
+
+/// The name of a scenario, with the line of its examples row for an outline
+/// to tell the rows apart
+pub fn scenario_name(scenario: &Scenario) -> String {
+  match scenario.outline_line {
+    Some(_) => format!("{} (line {})", scenario.name, scenario.line),
+    None => scenario.name.clone(),
+  }
+}
+
+/// A listener to the events of a native run, such as a report format
+///
+/// Every method does nothing by default, so implementers only need to handle
+/// the events they report on. Several listeners can be told about a run at
+/// once by collecting them in a `Vec<Box<Formatter>>`, and custom ones are
+/// added to the built in formats with
+/// [CucumberConfig::listener](../struct.CucumberConfig.html#method.listener).
+pub trait Formatter {
+  /// Called once before anything runs, with every feature of the run
+  fn run_started(&mut self, _features: &[Feature]) {}
+
+  fn feature_started(&mut self, _feature: &Feature) {}
+
+  fn scenario_started(&mut self, _feature: &Feature, _scenario: &Scenario) {}
+
+  /// Called for every Before and After hook, as it finishes
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, _result: &HookResult) {}
+
+  /// Called for every step before it runs, with the step definitions it
+  /// matched. Steps are only run if they matched exactly one.
+  fn step_matched(&mut self,
+                  _feature: &Feature,
+                  _scenario: &Scenario,
+                  _step: &Step,
+                  _matches: &[MatchedStep]) {
+  }
+
+  /// Called for every step of a scenario, background steps included
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   _step: &Step,
+                   _result: &StepResult) {
+  }
+
+  fn scenario_finished(&mut self,
+                       _feature: &Feature,
+                       _scenario: &Scenario,
+                       _result: &ScenarioResult) {
+  }
+
+  fn feature_finished(&mut self, _feature: &Feature) {}
+
+  fn run_finished(&mut self, _summary: &Summary) {}
+}
+
+/// Several formatters, which are all told about every event
+impl Formatter for Vec<Box<Formatter>> {
+  fn run_started(&mut self, features: &[Feature]) {
+    for formatter in self.iter_mut() {
+      formatter.run_started(features);
+    }
+  }
+
+  fn feature_started(&mut self, feature: &Feature) {
+    for formatter in self.iter_mut() {
+      formatter.feature_started(feature);
+    }
+  }
+
+  fn scenario_started(&mut self, feature: &Feature, scenario: &Scenario) {
+    for formatter in self.iter_mut() {
+      formatter.scenario_started(feature, scenario);
+    }
+  }
+
+  fn hook_finished(&mut self, feature: &Feature, scenario: &Scenario, result: &HookResult) {
+    for formatter in self.iter_mut() {
+      formatter.hook_finished(feature, scenario, result);
+    }
+  }
+
+  fn step_matched(&mut self,
+                  feature: &Feature,
+                  scenario: &Scenario,
+                  step: &Step,
+                  matches: &[MatchedStep]) {
+    for formatter in self.iter_mut() {
+      formatter.step_matched(feature, scenario, step, matches);
+    }
+  }
+
+  fn step_finished(&mut self,
+                   feature: &Feature,
+                   scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    for formatter in self.iter_mut() {
+      formatter.step_finished(feature, scenario, step, result);
+    }
+  }
+
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    for formatter in self.iter_mut() {
+      formatter.scenario_finished(feature, scenario, result);
+    }
+  }
+
+  fn feature_finished(&mut self, feature: &Feature) {
+    for formatter in self.iter_mut() {
+      formatter.feature_finished(feature);
+    }
+  }
+
+  fn run_finished(&mut self, summary: &Summary) {
+    for formatter in self.iter_mut() {
+      formatter.run_finished(summary);
+    }
+  }
+}
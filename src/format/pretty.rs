@@ -0,0 +1,405 @@
+//! Human readable, optionally colourised, console output
+
+use std::env;
+use std::io::{self, Stdout, Write};
+use std::time::Duration;
+
+use atty;
+
+use format::Formatter;
+use gherkin::{Feature, Scenario, Step, StepArgument};
+use event::response::InvokeResponse;
+use native::{HookResult, ScenarioResult, StatusCounts, StepResult, StepStatus, Summary};
+
+const GREY: &'static str = "90";
+const CYAN: &'static str = "36";
+
+// The order statuses are listed in by the summary
+const SUMMARY_ORDER: &'static [StepStatus] = &[StepStatus::Failed,
+                                               StepStatus::Ambiguous,
+                                               StepStatus::Skipped,
+                                               StepStatus::Undefined,
+                                               StepStatus::Pending,
+                                               StepStatus::Passed];
+
+/// Prints features, scenarios and steps as they run, in the manner of
+/// Cucumber's `pretty` formatter
+///
+/// Each step is printed with the location of the step definition it matched,
+/// and followed by its docstring or table, and by the message of a failure. A
+/// summary of scenario and step counts ends the run, along with snippets for
+/// any undefined steps. Every attempt of a retried scenario is printed.
+pub struct Pretty<W: Write> {
+  out: W,
+  colour: bool,
+  // The column at which location comments start in the current scenario
+  comment_column: usize,
+  snippets: Vec<String>,
+}
+
+impl Pretty<Stdout> {
+  /// Print to stdout, with colour unless it is not a terminal or the
+  /// `NO_COLOR` environment variable is set
+  pub fn stdout() -> Pretty<Stdout> {
+    let no_color = env::var_os("NO_COLOR").map(|value| !value.is_empty()).unwrap_or(false);
+    Pretty::new(io::stdout(), !no_color && atty::is(atty::Stream::Stdout))
+  }
+}
+
+impl<W: Write> Pretty<W> {
+  pub fn new(out: W, colour: bool) -> Pretty<W> {
+    Pretty {
+      out: out,
+      colour: colour,
+      comment_column: 0,
+      snippets: Vec::new(),
+    }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  fn paint(&self, colour: &str, text: &str) -> String {
+    if self.colour {
+      format!("\x1b[{}m{}\x1b[0m", colour, text)
+    } else {
+      text.to_owned()
+    }
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn line(&mut self, text: &str) {
+    let _ = writeln!(self.out, "{}", text);
+  }
+
+  fn commented(&mut self, text: String, colour: Option<&str>, comment: &str) {
+    let padding = self.comment_column.saturating_sub(text.chars().count());
+    let line = format!("{}{} {}",
+                       colour.map(|colour| self.paint(colour, &text)).unwrap_or(text.clone()),
+                       " ".repeat(padding),
+                       self.paint(GREY, &format!("# {}", comment)));
+    self.line(&line);
+  }
+
+  fn tags(&mut self, indent: &str, tags: &[String]) {
+    if !tags.is_empty() {
+      let line = format!("{}{}", indent, self.paint(CYAN, &tags.join(" ")));
+      self.line(&line);
+    }
+  }
+
+  fn argument(&mut self, colour: &str, argument: &StepArgument) {
+    match argument {
+      &StepArgument::DocString(ref content) => {
+        let mut lines = vec!["      \"\"\"".to_owned()];
+        lines.extend(content.lines().map(|line| format!("      {}", line)));
+        lines.push("      \"\"\"".to_owned());
+        for line in lines {
+          let line = self.paint(colour, &line);
+          self.line(&line);
+        }
+      },
+      &StepArgument::Table(ref rows) => {
+        let widths: Vec<usize> = (0..rows[0].len())
+          .map(|col| rows.iter().map(|row| row[col].chars().count()).max().unwrap_or(0))
+          .collect();
+        for row in rows {
+          let cells: Vec<String> = row.iter()
+            .zip(widths.iter())
+            .map(|(cell, &width)| {
+              format!(" {}{} ", cell, " ".repeat(width - cell.chars().count()))
+            })
+            .collect();
+          let line = self.paint(colour, &format!("      |{}|", cells.join("|")));
+          self.line(&line);
+        }
+      },
+    }
+  }
+
+  // The message of a failed or pending step or hook
+  fn message(&mut self, colour: &str, response: Option<&InvokeResponse>) {
+    let message = match response {
+      Some(&InvokeResponse::Fail(ref failure)) if failure.exception().is_empty() => {
+        failure.message().to_owned()
+      },
+      Some(&InvokeResponse::Fail(ref failure)) => {
+        format!("{} ({})", failure.message(), failure.exception())
+      },
+      Some(&InvokeResponse::Pending(ref pending)) => format!("{} (pending)", pending),
+      _ => return,
+    };
+    for line in message.lines() {
+      let line = self.paint(colour, &format!("      {}", line));
+      self.line(&line);
+    }
+  }
+
+  fn counts(&self, noun: &str, counts: &StatusCounts) -> String {
+    let details: Vec<String> = SUMMARY_ORDER.iter()
+      .filter(|&&status| counts.get(status) > 0)
+      .map(|&status| {
+        self.paint(status_colour(status),
+                   &format!("{} {}", counts.get(status), status.as_str()))
+      })
+      .collect();
+
+    let total = counts.total();
+    let noun = if total == 1 { noun.to_owned() } else { format!("{}s", noun) };
+    if details.is_empty() {
+      format!("{} {}", total, noun)
+    } else {
+      format!("{} {} ({})", total, noun, details.join(", "))
+    }
+  }
+}
+
+fn status_colour(status: StepStatus) -> &'static str {
+  match status {
+    StepStatus::Passed => "32",
+    StepStatus::Failed | StepStatus::Ambiguous => "31",
+    StepStatus::Pending | StepStatus::Undefined => "33",
+    StepStatus::Skipped => CYAN,
+  }
+}
+
+fn step_line(step: &Step) -> String {
+  format!("    {} {}", step.keyword, step.text)
+}
+
+fn scenario_line(scenario: &Scenario) -> String {
+  format!("  {}: {}", scenario.keyword, scenario.name)
+}
+
+/// Format a duration as Cucumber does, such as `1m2.345s`
+pub fn format_duration(duration: Duration) -> String {
+  format!("{}m{}.{:03}s",
+          duration.as_secs() / 60,
+          duration.as_secs() % 60,
+          duration.subsec_nanos() / 1000000)
+}
+
+impl<W: Write> Formatter for Pretty<W> {
+  fn feature_started(&mut self, feature: &Feature) {
+    self.tags("", &feature.tags);
+    let line = format!("Feature: {}", feature.name);
+    self.line(&line);
+    for description in feature.description.lines() {
+      let line = format!("  {}", description);
+      self.line(&line);
+    }
+    self.line("");
+  }
+
+  fn scenario_started(&mut self, feature: &Feature, scenario: &Scenario) {
+    self.comment_column = feature.background_steps(scenario)
+      .into_iter()
+      .chain(scenario.steps.iter())
+      .map(|step| step_line(step).chars().count())
+      .chain(Some(scenario_line(scenario).chars().count()))
+      .max()
+      .unwrap();
+
+    // The feature's tags were printed with it
+    let tags = feature.scenario_tags(scenario).split_off(feature.tags.len());
+    self.tags("  ", &tags);
+    self.commented(scenario_line(scenario),
+                   None,
+                   &format!("{}:{}", feature.path, scenario.line));
+  }
+
+  // NOTE: Hooks are only printed when they did not pass, as they are not
+  // part of the feature
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    if result.status != StepStatus::Passed {
+      let colour = status_colour(result.status);
+      self.commented(format!("    {} hook", result.kind.as_str()), Some(colour), &result.source);
+      self.message(colour, Some(&result.response));
+    }
+  }
+
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    let colour = status_colour(result.status);
+
+    match result.matched {
+      Some(ref matched) => self.commented(step_line(step), Some(colour), &matched.source),
+      None => {
+        let line = self.paint(colour, &step_line(step));
+        self.line(&line);
+      },
+    }
+
+    if let Some(ref argument) = step.argument {
+      self.argument(colour, argument);
+    }
+
+    for line in result.output.lines() {
+      let line = self.paint(GREY, &format!("      {}", line));
+      self.line(&line);
+    }
+
+    self.message(colour, result.response.as_ref());
+
+    if let Some(ref snippet) = result.snippet {
+      if !self.snippets.contains(snippet) {
+        self.snippets.push(snippet.clone());
+      }
+    }
+  }
+
+  fn scenario_finished(&mut self,
+                       _feature: &Feature,
+                       _scenario: &Scenario,
+                       result: &ScenarioResult) {
+    if result.retried {
+      let line = self.paint(status_colour(StepStatus::Pending),
+                            &format!("    Attempt {} failed, retrying", result.attempt));
+      self.line(&line);
+    } else if result.flaky() {
+      let line = self.paint(status_colour(StepStatus::Pending),
+                            &format!("    Passed on attempt {}, flaky", result.attempt));
+      self.line(&line);
+    }
+    self.line("");
+  }
+
+  fn run_finished(&mut self, summary: &Summary) {
+    let scenarios = self.counts("scenario", &summary.scenarios);
+    let steps = self.counts("step", &summary.steps);
+    self.line(&scenarios);
+    if summary.flaky > 0 {
+      let noun = if summary.flaky == 1 { "scenario" } else { "scenarios" };
+      let flaky = self.paint(status_colour(StepStatus::Pending),
+                             &format!("{} flaky {} passed after being retried",
+                                      summary.flaky,
+                                      noun));
+      self.line(&flaky);
+    }
+    self.line(&steps);
+    self.line(&format_duration(summary.duration));
+    for failure in &summary.after_all_failures {
+      let failure = self.paint(status_colour(StepStatus::Failed), failure);
+      self.line(&failure);
+    }
+
+    if !self.snippets.is_empty() {
+      let snippets = self.snippets.join("\n\n");
+      self.line("");
+      let heading = self.paint(status_colour(StepStatus::Undefined),
+                               "You can implement step definitions for undefined steps with \
+                                these snippets:");
+      self.line(&heading);
+      self.line("");
+      let snippets = self.paint(status_colour(StepStatus::Undefined), &snippets);
+      self.line(&snippets);
+    }
+
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use std::time::Duration;
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use native::test::{flaky_step, formatter_runner, run_with};
+
+  fn run(colour: bool) -> String {
+    run_with(&mut formatter_runner(),
+             "@slow\nFeature: Pretty\n  Output for people\n\n  Scenario: Working\n    Given it \
+              works\n      | a | bb |\n\n  Scenario: Broken\n    Then it breaks\n    And a \
+              missing step\n",
+             |out| Pretty::new(out, colour))
+  }
+
+  #[test]
+  fn it_prints_features_scenarios_and_steps() {
+    let output = run(false);
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(&lines[..12],
+               &["@slow",
+                 "Feature: Pretty",
+                 "  Output for people",
+                 "",
+                 "  Scenario: Working # test.feature:5",
+                 "    Given it works  # steps.rs:2",
+                 "      | a | bb |",
+                 "",
+                 "  Scenario: Broken     # test.feature:9",
+                 "    Then it breaks     # steps.rs:3",
+                 "      it broke",
+                 "    And a missing step"]);
+    assert_eq!(lines[13], "2 scenarios (1 failed, 1 passed)");
+    assert_eq!(lines[14], "3 steps (1 failed, 1 undefined, 1 passed)");
+    assert!(output.contains("You can implement step definitions for undefined steps"));
+    assert!(output.contains("Then!(cuke, \"^a missing step$\""));
+    assert!(!output.contains("\x1b["));
+  }
+
+  #[test]
+  fn it_colours_statuses() {
+    let output = run(true);
+    assert!(output.contains("\x1b[32m    Given it works\x1b[0m"));
+    assert!(output.contains("\x1b[31m      it broke\x1b[0m"));
+    assert!(output.contains("\x1b[33m    And a missing step\x1b[0m"));
+  }
+
+  #[test]
+  fn it_prints_failed_hooks() {
+    let mut runner = formatter_runner();
+    runner.before("hooks.rs", 1, None, Box::new(|_, _| ()));
+    runner.after("hooks.rs", 2, None, Box::new(|c, _| c.fail("cleanup failed")));
+
+    let output = run_with(&mut runner,
+                          "Feature: Hooks\n  Scenario: Cleaning\n    Given it works\n",
+                          |out| Pretty::new(out, false));
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(&lines[2..6],
+               &["  Scenario: Cleaning # test.feature:2",
+                 "    Given it works   # steps.rs:2",
+                 "    After hook       # hooks.rs:2",
+                 "      cleanup failed"]);
+    assert!(output.contains("1 scenario (1 failed)"));
+  }
+
+  #[test]
+  fn it_prints_retries() {
+    let mut runner = formatter_runner().retry(1);
+    runner.given("steps.rs", 5, cucumber_regex::build("^a flaky step$"), flaky_step());
+
+    let output = run_with(&mut runner,
+                          "Feature: Retries\n  Scenario: Flaky\n    Given a flaky step\n",
+                          |out| Pretty::new(out, false));
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(&lines[2..12],
+               &["  Scenario: Flaky      # test.feature:2",
+                 "    Given a flaky step # steps.rs:5",
+                 "      not this time",
+                 "    Attempt 1 failed, retrying",
+                 "",
+                 "  Scenario: Flaky      # test.feature:2",
+                 "    Given a flaky step # steps.rs:5",
+                 "    Passed on attempt 2, flaky",
+                 "",
+                 "1 scenario (1 passed)"]);
+    assert_eq!(lines[12], "1 flaky scenario passed after being retried");
+  }
+
+  #[test]
+  fn it_formats_durations() {
+    assert_eq!(format_duration(Duration::from_millis(62345)), "1m2.345s");
+  }
+}
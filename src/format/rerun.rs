@@ -0,0 +1,98 @@
+//! A list of the scenarios that failed, to run them again
+
+use std::io::Write;
+
+use format::Formatter;
+use gherkin::{Feature, Scenario};
+use native::{ScenarioResult, StepStatus, Strictness, Summary};
+
+/// Writes `path:line` for every failed or ambiguous scenario, with the lines
+/// of a feature file joined as in `features/a.feature:12:30`, one file per
+/// line
+///
+/// The line of a row of examples is written for outlines, so only the rows
+/// that failed are run again. A retried scenario is only written if its last
+/// attempt failed, and nothing is written when no scenario failed. Pending and
+/// undefined scenarios are written too when the run was strict about them.
+pub struct Rerun<W: Write> {
+  out: W,
+  // The path, line and status of every scenario that could fail the run
+  failed: Vec<(String, usize, StepStatus)>,
+}
+
+impl<W: Write> Rerun<W> {
+  pub fn new(out: W) -> Rerun<W> {
+    Rerun {
+      out: out,
+      failed: Vec::new(),
+    }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+}
+
+impl<W: Write> Formatter for Rerun<W> {
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    // NOTE: Whether the run was strict is only known once it is finished
+    if Strictness::strict().fails(result.status) && !result.retried {
+      self.failed.push((feature.path.clone(), scenario.line, result.status));
+    }
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn run_finished(&mut self, summary: &Summary) {
+    let mut files: Vec<(&str, Vec<String>)> = Vec::new();
+    for &(ref path, line, status) in &self.failed {
+      if !summary.strictness.fails(status) {
+        continue;
+      }
+      if files.last().map_or(true, |&(last, _)| last != path) {
+        files.push((path, Vec::new()));
+      }
+      files.last_mut().unwrap().1.push(line.to_string());
+    }
+
+    for (path, lines) in files {
+      let _ = writeln!(self.out, "{}:{}", path, lines.join(":"));
+    }
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use native::test::{formatter_runner, run_features};
+
+  #[test]
+  fn it_lists_failed_scenarios() {
+    let mut runner = formatter_runner();
+    runner.then("steps.rs", 5, cucumber_regex::build("^it is ambiguous$"), Box::new(|_, _, _| ()));
+    runner.then("steps.rs", 6, cucumber_regex::build("^it is (\\w+)$"), Box::new(|_, _, _| ()));
+
+    let features = [("a.feature",
+                     "Feature: A\n  Scenario: Working\n    Given it works\n  Scenario: \
+                      Broken\n    Then it breaks\n  Scenario Outline: Rows\n    Given <x>\n    \
+                      Examples:\n      | x |\n      | it works |\n      | it breaks |\n"),
+                    ("b.feature", "Feature: B\n  Scenario: Working\n    Given it works\n"),
+                    ("c.feature",
+                     "Feature: C\n  Scenario: Missing\n    Given it went missing\n  Scenario: \
+                      Ambiguous\n    Then it is ambiguous\n")];
+
+    assert_eq!(run_features(&mut runner, &features, Rerun::new),
+               "a.feature:4:11\nc.feature:4\n");
+
+    let mut runner = runner.strict(Strictness::strict());
+    assert_eq!(run_features(&mut runner, &features, Rerun::new),
+               "a.feature:4:11\nc.feature:2:4\n");
+  }
+}
@@ -0,0 +1,183 @@
+//! [Test Anything Protocol](https://testanything.org) output
+
+use std::io::Write;
+
+use serde_json;
+
+use format::{scenario_name, Formatter};
+use gherkin::{Feature, Scenario, Step};
+use native::{HookResult, ScenarioResult, StepResult, StepStatus, Summary};
+
+/// Reports each scenario as a TAP version 13 test point
+///
+/// Failed and ambiguous scenarios are `not ok`, with the failing step and its
+/// message in a YAML block. Pending scenarios are reported as TODO and
+/// undefined ones as skipped. Failed attempts of a retried scenario are
+/// reported as comments before its test point. A run stopped by fail fast
+/// ends with `Bail out!`, as it has fewer test points than it planned.
+pub struct Tap<W: Write> {
+  out: W,
+  planned: usize,
+  number: usize,
+  // The step or hook that failed in the current scenario, and its message
+  failure: Option<(String, String)>,
+}
+
+impl<W: Write> Tap<W> {
+  pub fn new(out: W) -> Tap<W> {
+    Tap {
+      out: out,
+      planned: 0,
+      number: 0,
+      failure: None,
+    }
+  }
+
+  /// Stop formatting, yielding the output
+  pub fn into_inner(self) -> W {
+    self.out
+  }
+
+  // NOTE: Formatting should never break a run, so write errors are ignored
+  fn line(&mut self, text: &str) {
+    let _ = writeln!(self.out, "{}", text);
+  }
+}
+
+impl<W: Write> Formatter for Tap<W> {
+  fn run_started(&mut self, features: &[Feature]) {
+    self.planned = features.iter().map(|feature| feature.scenarios.len()).sum::<usize>();
+    self.line("TAP version 13");
+    let plan = format!("1..{}", self.planned);
+    self.line(&plan);
+  }
+
+  fn scenario_started(&mut self, _feature: &Feature, _scenario: &Scenario) {
+    self.failure = None;
+  }
+
+  fn hook_finished(&mut self, _feature: &Feature, _scenario: &Scenario, result: &HookResult) {
+    if let Some(failure) = result.failure() {
+      if self.failure.is_none() {
+        let hook = format!("{} hook {}", result.kind.as_str(), result.source);
+        self.failure = Some((hook, failure.message().to_owned()));
+      }
+    }
+  }
+
+  fn step_finished(&mut self,
+                   _feature: &Feature,
+                   _scenario: &Scenario,
+                   step: &Step,
+                   result: &StepResult) {
+    let message = result.failure()
+      .map(|failure| failure.message())
+      .or(result.pending_message());
+    if let Some(message) = message {
+      self.failure = Some((format!("{} {}", step.keyword, step.text), message.to_owned()));
+    }
+  }
+
+  fn scenario_finished(&mut self,
+                       feature: &Feature,
+                       scenario: &Scenario,
+                       result: &ScenarioResult) {
+    let failure = self.failure.take();
+    if result.retried {
+      let message = failure.map(|(step, message)| format!(" at {}: {}", step, message));
+      self.line(&format!("# {}: {} failed on attempt {}{}, retrying",
+                         feature.name,
+                         scenario_name(scenario),
+                         result.attempt,
+                         message.unwrap_or(String::new()).replace('\n', " ")));
+      return;
+    }
+
+    self.number += 1;
+    let description = format!("{} - {}: {}", self.number, feature.name, scenario_name(scenario));
+
+    match result.status {
+      StepStatus::Passed if result.flaky() => {
+        self.line(&format!("ok {}", description));
+        self.line(&format!("# flaky, passed on attempt {}", result.attempt));
+      },
+      StepStatus::Passed => self.line(&format!("ok {}", description)),
+      StepStatus::Failed | StepStatus::Ambiguous => {
+        self.line(&format!("not ok {}", description));
+        if let Some((step, message)) = failure {
+          self.line("  ---");
+          self.line(&format!("  message: {}", serde_json::to_string(&message).unwrap()));
+          self.line(&format!("  severity: {}", result.status.as_str()));
+          self.line(&format!("  step: {}", serde_json::to_string(&step).unwrap()));
+          self.line("  ...");
+        }
+      },
+      StepStatus::Pending => {
+        let reason = failure.map(|(_, message)| message).unwrap_or("pending".to_owned());
+        self.line(&format!("not ok {} # TODO {}", description, reason));
+      },
+      status => self.line(&format!("ok {} # SKIP {}", description, status.as_str())),
+    }
+  }
+
+  fn run_finished(&mut self, _summary: &Summary) {
+    if self.number < self.planned {
+      let bail_out = format!("Bail out! Stopped after {} of {} scenarios",
+                             self.number,
+                             self.planned);
+      self.line(&bail_out);
+    }
+    let _ = self.out.flush();
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use native::test::{flaky_step, formatter_runner, run_with};
+
+  #[test]
+  fn it_writes_test_points() {
+    let mut runner = formatter_runner();
+    runner.then("steps.rs",
+                5,
+                cucumber_regex::build("^it breaks loudly$"),
+                Box::new(|c, _, _| c.fail("it \"broke\"")));
+
+    assert_eq!(run_with(&mut runner,
+                        "Feature: TAP\n  Scenario: Working\n    Given it works\n  Scenario: \
+                         Broken\n    Given it works\n    Then it breaks loudly\n  Scenario: \
+                         Waiting\n    Then it waits\n  Scenario: Missing\n    Then it is \
+                         missing\n",
+                        Tap::new),
+               "TAP version 13\n1..4\nok 1 - TAP: Working\nnot ok 2 - TAP: Broken\n  ---\n  \
+                message: \"it \\\"broke\\\"\"\n  severity: failed\n  step: \"Then it breaks \
+                loudly\"\n  ...\nnot ok 3 - TAP: Waiting # TODO later\nok 4 - TAP: Missing # \
+                SKIP undefined\n");
+  }
+
+  #[test]
+  fn it_comments_on_retries() {
+    let mut runner = formatter_runner().retry(1);
+    runner.given("steps.rs", 5, cucumber_regex::build("^it is flaky$"), flaky_step());
+
+    assert_eq!(run_with(&mut runner,
+                        "Feature: TAP\n  Scenario: Flaky\n    Given it is flaky\n",
+                        Tap::new),
+               "TAP version 13\n1..1\n# TAP: Flaky failed on attempt 1 at Given it is flaky: not \
+                this time, retrying\nok 1 - TAP: Flaky\n# flaky, passed on attempt 2\n");
+  }
+
+  #[test]
+  fn it_bails_out_of_runs_stopped_early() {
+    let output = run_with(&mut formatter_runner().fail_fast(true),
+                          "Feature: TAP\n  Scenario: Broken\n    Then it breaks\n  Scenario: \
+                           Unrun\n    Then it breaks\n",
+                          Tap::new);
+    assert!(output.starts_with("TAP version 13\n1..2\nnot ok 1 - TAP: Broken\n"));
+    assert!(output.ends_with("  ...\nBail out! Stopped after 1 of 2 scenarios\n"));
+  }
+}
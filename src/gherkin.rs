@@ -0,0 +1,785 @@
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+use tags::Tags;
+
+/// A parsed `.feature` file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Feature {
+  pub name: String,
+  pub description: String,
+  pub tags: Vec<String>,
+  /// The line of each of the tags
+  pub tag_lines: Vec<usize>,
+  /// The file the feature was read from
+  pub path: String,
+  pub line: usize,
+  /// The line of the feature's Background, if it has one
+  pub background_line: Option<usize>,
+  /// Steps run before every scenario of the feature
+  pub background: Vec<Step>,
+  /// The scenarios of the feature, with outlines expanded into one scenario
+  /// per row of examples, and those of rules following on from those outside
+  /// of any rule
+  pub scenarios: Vec<Scenario>,
+}
+
+impl Feature {
+  /// The tags that apply to a scenario of this feature, including those
+  /// inherited from the feature, its rule and its examples
+  ///
+  /// These are the names of [Tags::of_scenario](../tags/struct.Tags.html#method.of_scenario).
+  pub fn scenario_tags(&self, scenario: &Scenario) -> Vec<String> {
+    Tags::of_scenario(self, scenario).names()
+  }
+
+  /// The background steps run before a scenario of this feature, those of the
+  /// feature followed by those of the scenario's rule
+  pub fn background_steps<'a>(&'a self, scenario: &'a Scenario) -> Vec<&'a Step> {
+    self.background.iter().chain(scenario.rule_background.iter()).collect()
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scenario {
+  /// The keyword the scenario was declared with, such as `Scenario` or
+  /// `Scenario Outline`
+  pub keyword: String,
+  pub name: String,
+  /// The tags of the scenario, or of its outline
+  pub tags: Vec<String>,
+  /// The line of each of the tags
+  pub tag_lines: Vec<usize>,
+  /// The tags of the rule the scenario belongs to, if any
+  pub rule_tags: Vec<String>,
+  /// The line of each of the rule's tags
+  pub rule_tag_lines: Vec<usize>,
+  /// The line of the Background of the scenario's rule, if it has one
+  pub rule_background_line: Option<usize>,
+  /// Steps run after the feature's background, from the Background of the
+  /// scenario's rule
+  pub rule_background: Vec<Step>,
+  /// The tags of the examples of the row, for an outline
+  pub examples_tags: Vec<String>,
+  /// The line of each of the examples' tags
+  pub examples_tag_lines: Vec<usize>,
+  /// The line of the scenario, or of its row of examples for an outline
+  pub line: usize,
+  /// The line of the outline this scenario was expanded from, if any
+  pub outline_line: Option<usize>,
+  pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+  /// The keyword as written, such as `Given`, `And` or `*`
+  pub keyword: String,
+  pub text: String,
+  pub line: usize,
+  pub argument: Option<StepArgument>,
+}
+
+/// The docstring or table following a step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepArgument {
+  DocString(String),
+  Table(Vec<Vec<String>>),
+}
+
+impl StepArgument {
+  /// The name Cucumber uses for the type of the argument, as found in
+  /// [SnippetTextRequests](../event/request/struct.SnippetTextRequest.html)
+  pub fn multiline_arg_class(&self) -> &'static str {
+    match self {
+      &StepArgument::DocString(_) => "Cucumber::MultilineArgument::DocString",
+      &StepArgument::Table(_) => "Cucumber::MultilineArgument::DataTable",
+    }
+  }
+}
+
+/// A `.feature` file that could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+  pub path: String,
+  pub line: usize,
+  pub message: String,
+}
+
+impl fmt::Display for ParseError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}:{}: {}", self.path, self.line, self.message)
+  }
+}
+
+impl Error for ParseError {
+  fn description(&self) -> &str {
+    &self.message
+  }
+}
+
+/// Reasons features could not be loaded from disk
+#[derive(Debug)]
+pub enum LoadError {
+  Io(String, io::Error),
+  Parse(ParseError),
+}
+
+impl fmt::Display for LoadError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      &LoadError::Io(ref path, ref err) => write!(f, "{}: {}", path, err),
+      &LoadError::Parse(ref err) => err.fmt(f),
+    }
+  }
+}
+
+impl From<ParseError> for LoadError {
+  fn from(err: ParseError) -> LoadError {
+    LoadError::Parse(err)
+  }
+}
+
+/// Read a `.feature` file, or every `.feature` file below a directory
+///
+/// Files in a directory are read in the order of their paths, so that runs are
+/// repeatable.
+pub fn read_features<P: AsRef<Path>>(path: P) -> Result<Vec<Feature>, LoadError> {
+  let path = path.as_ref();
+  let display = path.to_string_lossy().into_owned();
+
+  if path.is_dir() {
+    let mut entries: Vec<PathBuf> = try!(fs::read_dir(path)
+      .and_then(|entries| entries.map(|entry| entry.map(|e| e.path())).collect())
+      .map_err(|err| LoadError::Io(display.clone(), err)));
+    entries.sort();
+
+    let mut features = Vec::new();
+    for entry in entries {
+      if entry.is_dir() || entry.extension().map(|ext| ext == "feature").unwrap_or(false) {
+        features.extend(try!(read_features(entry)));
+      }
+    }
+    Ok(features)
+  } else {
+    let mut source = String::new();
+    try!(File::open(path)
+      .and_then(|mut file| file.read_to_string(&mut source))
+      .map_err(|err| LoadError::Io(display.clone(), err)));
+    Ok(vec![try!(parse(&display, &source))])
+  }
+}
+
+// The block of the feature that lines are currently being added to
+enum Block {
+  Feature,
+  Rule,
+  Background,
+  Scenario(Scenario),
+  Outline(Scenario, Vec<Scenario>),
+  Examples(Scenario, Vec<Scenario>, TagLines, Option<Vec<String>>),
+}
+
+// Tags, along with the line of each
+type TagLines = (Vec<String>, Vec<usize>);
+
+struct Parser<'a> {
+  path: &'a str,
+  feature: Option<Feature>,
+  block: Block,
+  tags: TagLines,
+  // The tags of the rule the parser is in, if any
+  rule_tags: Option<TagLines>,
+  // The background of the rule the parser is in, and how many scenarios the
+  // feature had before the rule
+  rule_background_line: Option<usize>,
+  rule_background: Vec<Step>,
+  rule_start: usize,
+}
+
+/// Parse the source of a `.feature` file
+///
+/// Supports features with a description and tags, a background, scenarios,
+/// scenario outlines with tagged examples and rules, along with docstring and
+/// table step arguments. Rules are flattened into the feature, their scenarios
+/// keeping the rule's tags and background. The path is only used for error
+/// messages and to fill in the feature's path.
+///
+/// # Example
+///
+/// ```
+/// use cucumber::gherkin;
+///
+/// fn main() {
+///   let feature = gherkin::parse("calculator.feature",
+///                                "Feature: Calculator\n  Scenario: Adding\n    Given I have \
+///                                 entered 5\n    When I press add\n")
+///     .unwrap();
+///   assert_eq!(feature.scenarios[0].steps[1].text, "I press add");
+/// }
+/// ```
+pub fn parse(path: &str, source: &str) -> Result<Feature, ParseError> {
+  let mut parser = Parser {
+    path: path,
+    feature: None,
+    block: Block::Feature,
+    tags: (Vec::new(), Vec::new()),
+    rule_tags: None,
+    rule_background_line: None,
+    rule_background: Vec::new(),
+    rule_start: 0,
+  };
+
+  let lines: Vec<&str> = source.lines().collect();
+  let mut idx = 0;
+  while idx < lines.len() {
+    let line_number = idx + 1;
+    let line = lines[idx].trim();
+    idx += 1;
+
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+
+    if line.starts_with("\"\"\"") || line.starts_with("```") {
+      let delimiter = &line[..3];
+      // NOTE: Indentation is counted in characters, as whitespace such as a
+      // no-break space takes more than one byte
+      let indent = lines[idx - 1].chars().take_while(|c| c.is_whitespace()).count();
+      let mut content = Vec::new();
+
+      loop {
+        if idx >= lines.len() {
+          return Err(parser.error(line_number, "Unterminated docstring"));
+        }
+        let content_line = lines[idx];
+        idx += 1;
+        if content_line.trim() == delimiter {
+          break;
+        }
+        let strip: usize = content_line.chars()
+          .take(indent)
+          .take_while(|c| c.is_whitespace())
+          .map(|c| c.len_utf8())
+          .sum();
+        let content_line = &content_line[strip..];
+        content.push(content_line.replace("\\\"\\\"\\\"", "\"\"\"").replace("\\`\\`\\`", "```"));
+      }
+
+      try!(parser.docstring(line_number, content.join("\n")));
+    } else if line.starts_with('|') {
+      try!(parser.table_row(line_number, line));
+    } else if line.starts_with('@') {
+      for tag in line.split('#').next().unwrap().split_whitespace() {
+        parser.tags.0.push(tag.to_owned());
+        parser.tags.1.push(line_number);
+      }
+    } else if let Some((keyword, name)) = keyword_line(line) {
+      try!(parser.keyword(line_number, keyword, name));
+    } else if let (Some(keyword), true) = (step_keyword(line), parser.steps().is_some()) {
+      let text = line[keyword.len()..].trim();
+      try!(parser.step(line_number, keyword, text));
+    } else {
+      try!(parser.description(line_number, line));
+    }
+  }
+
+  parser.finish()
+}
+
+const STEP_KEYWORDS: &'static [&'static str] = &["Given", "When", "Then", "And", "But", "*"];
+
+fn keyword_line(line: &str) -> Option<(&str, &str)> {
+  line.find(':').and_then(|colon| {
+    let keyword = &line[..colon];
+    match keyword {
+      "Feature" | "Rule" | "Background" | "Scenario" | "Example" | "Scenario Outline" |
+      "Scenario Template" | "Examples" | "Scenarios" => Some((keyword, line[colon + 1..].trim())),
+      _ => None,
+    }
+  })
+}
+
+fn step_keyword(line: &str) -> Option<&'static str> {
+  STEP_KEYWORDS.iter()
+    .find(|&&keyword| {
+      line.starts_with(keyword) &&
+      (keyword == "*" || line[keyword.len()..].starts_with(' ') || line.len() == keyword.len())
+    })
+    .cloned()
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+  let mut cells = Vec::new();
+  let mut cell = String::new();
+  let mut chars = line.trim().chars().skip(1);
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        match chars.next() {
+          Some('n') => cell.push('\n'),
+          Some(escaped) => cell.push(escaped),
+          None => cell.push('\\'),
+        }
+      },
+      '|' => {
+        cells.push(cell.trim().to_owned());
+        cell.clear();
+      },
+      c => cell.push(c),
+    }
+  }
+
+  cells
+}
+
+// Replaces the <placeholders> of an outline with the values of an examples row
+fn substitute(text: &str, header: &[String], row: &[String]) -> String {
+  header.iter().zip(row.iter()).fold(text.to_owned(), |text, (name, value)| {
+    text.replace(&format!("<{}>", name), value)
+  })
+}
+
+impl<'a> Parser<'a> {
+  fn error(&self, line: usize, message: &str) -> ParseError {
+    ParseError {
+      path: self.path.to_owned(),
+      line: line,
+      message: message.to_owned(),
+    }
+  }
+
+  fn feature(&mut self, line: usize) -> Result<&mut Feature, ParseError> {
+    let err = self.error(line, "Expected a Feature");
+    self.feature.as_mut().ok_or(err)
+  }
+
+  fn keyword(&mut self, line: usize, keyword: &str, name: &str) -> Result<(), ParseError> {
+    let tags = ::std::mem::replace(&mut self.tags, (Vec::new(), Vec::new()));
+
+    if keyword == "Feature" {
+      if self.feature.is_some() {
+        return Err(self.error(line, "Only one Feature is allowed per file"));
+      }
+      self.feature = Some(Feature {
+        name: name.to_owned(),
+        description: String::new(),
+        tags: tags.0,
+        tag_lines: tags.1,
+        path: self.path.to_owned(),
+        line: line,
+        background_line: None,
+        background: Vec::new(),
+        scenarios: Vec::new(),
+      });
+      return Ok(());
+    }
+
+    try!(self.feature(line));
+
+    let block = ::std::mem::replace(&mut self.block, Block::Feature);
+    self.block = match (keyword, block) {
+      ("Examples", Block::Outline(outline, expanded)) |
+      ("Scenarios", Block::Outline(outline, expanded)) |
+      ("Examples", Block::Examples(outline, expanded, _, _)) |
+      ("Scenarios", Block::Examples(outline, expanded, _, _)) => {
+        Block::Examples(outline, expanded, tags, None)
+      },
+      ("Examples", _) | ("Scenarios", _) => {
+        return Err(self.error(line, "Examples must follow a Scenario Outline"));
+      },
+      ("Rule", block) => {
+        try!(self.close(line, block));
+        self.rule_tags = Some(tags);
+        self.rule_background_line = None;
+        self.rule_background = Vec::new();
+        self.rule_start = self.feature.as_ref().unwrap().scenarios.len();
+        Block::Rule
+      },
+      (_, block) => {
+        try!(self.close(line, block));
+        let (rule_tags, rule_tag_lines) = self.rule_tags.clone().unwrap_or_default();
+        let scenario = Scenario {
+          keyword: keyword.to_owned(),
+          name: name.to_owned(),
+          tags: tags.0,
+          tag_lines: tags.1,
+          rule_tags: rule_tags,
+          rule_tag_lines: rule_tag_lines,
+          rule_background_line: self.rule_background_line,
+          rule_background: self.rule_background.clone(),
+          examples_tags: Vec::new(),
+          examples_tag_lines: Vec::new(),
+          line: line,
+          outline_line: None,
+          steps: Vec::new(),
+        };
+
+        match keyword {
+          // NOTE: As rules are flattened, the background of a rule is kept by
+          // each of its scenarios
+          "Background" if self.rule_tags.is_some() => {
+            if self.feature.as_ref().unwrap().scenarios.len() > self.rule_start {
+              return Err(self.error(line, "Background must come before any Scenario"));
+            }
+            self.rule_background_line = Some(line);
+            Block::Background
+          },
+          "Background" => {
+            if !self.feature.as_ref().unwrap().scenarios.is_empty() {
+              return Err(self.error(line, "Background must come before any Scenario"));
+            }
+            self.feature.as_mut().unwrap().background_line = Some(line);
+            Block::Background
+          },
+          "Scenario Outline" | "Scenario Template" => Block::Outline(scenario, Vec::new()),
+          _ => Block::Scenario(scenario),
+        }
+      },
+    };
+
+    Ok(())
+  }
+
+  fn steps(&mut self) -> Option<&mut Vec<Step>> {
+    match self.block {
+      Block::Feature | Block::Rule | Block::Examples(..) => None,
+      Block::Background if self.rule_tags.is_some() => Some(&mut self.rule_background),
+      Block::Background => self.feature.as_mut().map(|feature| &mut feature.background),
+      Block::Scenario(ref mut scenario) |
+      Block::Outline(ref mut scenario, _) => Some(&mut scenario.steps),
+    }
+  }
+
+  fn step(&mut self, line: usize, keyword: &str, text: &str) -> Result<(), ParseError> {
+    let err = self.error(line, "Steps must belong to a Background or Scenario");
+    let steps = try!(self.steps().ok_or(err));
+    steps.push(Step {
+      keyword: keyword.to_owned(),
+      text: text.to_owned(),
+      line: line,
+      argument: None,
+    });
+    Ok(())
+  }
+
+  fn docstring(&mut self, line: usize, content: String) -> Result<(), ParseError> {
+    let err = self.error(line, "A docstring must follow a step");
+    match self.steps().and_then(|steps| steps.last_mut()) {
+      Some(ref mut step) if step.argument.is_none() => {
+        step.argument = Some(StepArgument::DocString(content));
+        Ok(())
+      },
+      _ => Err(err),
+    }
+  }
+
+  fn table_row(&mut self, line: usize, text: &str) -> Result<(), ParseError> {
+    let cells = table_cells(text);
+    let row_err = self.error(line, "Examples row has a different number of cells");
+
+    if let Block::Examples(ref outline, ref mut expanded, ref tags, ref mut header) = self.block {
+      match header.clone() {
+        None => *header = Some(cells),
+        Some(header) => {
+          if header.len() != cells.len() {
+            return Err(row_err);
+          }
+          expanded.push(expand(outline, tags, &header, &cells, line));
+        },
+      }
+      return Ok(());
+    }
+
+    let err = self.error(line, "A table must follow a step");
+    match self.steps().and_then(|steps| steps.last_mut()) {
+      Some(ref mut step) => {
+        match step.argument {
+          None => step.argument = Some(StepArgument::Table(vec![cells])),
+          Some(StepArgument::Table(ref mut rows)) => {
+            if rows[0].len() != cells.len() {
+              return Err(err);
+            }
+            rows.push(cells)
+          },
+          Some(StepArgument::DocString(_)) => return Err(err),
+        }
+        Ok(())
+      },
+      None => Err(err),
+    }
+  }
+
+  fn description(&mut self, line: usize, text: &str) -> Result<(), ParseError> {
+    match self.block {
+      Block::Feature => {
+        let feature = try!(self.feature(line));
+        if !feature.description.is_empty() {
+          feature.description.push('\n');
+        }
+        feature.description.push_str(text);
+        Ok(())
+      },
+      // Free text in a rule, or in a scenario before its first step, is a
+      // description, which is of no use to the runner
+      Block::Rule => Ok(()),
+      Block::Scenario(ref scenario) |
+      Block::Outline(ref scenario, _) if scenario.steps.is_empty() => Ok(()),
+      Block::Background if self.rule_tags.is_some() && self.rule_background.is_empty() => Ok(()),
+      Block::Background if self.rule_tags.is_none() &&
+                           self.feature.as_ref().unwrap().background.is_empty() => Ok(()),
+      Block::Examples(_, _, _, None) => Ok(()),
+      _ => Err(self.error(line, &format!("Unexpected line: {}", text))),
+    }
+  }
+
+  fn close(&mut self, line: usize, block: Block) -> Result<(), ParseError> {
+    let feature = try!(self.feature(line));
+    match block {
+      Block::Feature | Block::Rule | Block::Background => {},
+      Block::Scenario(scenario) => feature.scenarios.push(scenario),
+      Block::Outline(_, expanded) |
+      Block::Examples(_, expanded, _, _) => feature.scenarios.extend(expanded),
+    }
+    Ok(())
+  }
+
+  fn finish(mut self) -> Result<Feature, ParseError> {
+    let block = ::std::mem::replace(&mut self.block, Block::Feature);
+    if self.feature.is_none() {
+      return Err(self.error(1, "Expected a Feature"));
+    }
+    try!(self.close(0, block));
+    Ok(self.feature.unwrap())
+  }
+}
+
+fn expand(outline: &Scenario,
+          tags: &TagLines,
+          header: &[String],
+          row: &[String],
+          line: usize)
+          -> Scenario {
+  let steps = outline.steps
+    .iter()
+    .map(|step| {
+      Step {
+        keyword: step.keyword.clone(),
+        text: substitute(&step.text, header, row),
+        line: step.line,
+        argument: step.argument.as_ref().map(|argument| {
+          match argument {
+            &StepArgument::DocString(ref content) => {
+              StepArgument::DocString(substitute(content, header, row))
+            },
+            &StepArgument::Table(ref rows) => {
+              StepArgument::Table(rows.iter()
+                .map(|cells| cells.iter().map(|cell| substitute(cell, header, row)).collect())
+                .collect())
+            },
+          }
+        }),
+      }
+    })
+    .collect();
+
+  Scenario {
+    keyword: outline.keyword.clone(),
+    name: substitute(&outline.name, header, row),
+    tags: outline.tags.clone(),
+    tag_lines: outline.tag_lines.clone(),
+    rule_tags: outline.rule_tags.clone(),
+    rule_tag_lines: outline.rule_tag_lines.clone(),
+    rule_background_line: outline.rule_background_line,
+    rule_background: outline.rule_background.clone(),
+    examples_tags: tags.0.clone(),
+    examples_tag_lines: tags.1.clone(),
+    line: line,
+    outline_line: Some(outline.line),
+    steps: steps,
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn it_parses_features_and_scenarios() {
+    let feature = parse("test.feature",
+                        "# A comment\n@billing\nFeature: Billing\n  In order to pay\n  As a \
+                         customer\n\n  Background:\n    Given a customer\n\n  @fast @smoke\n  \
+                         Scenario: Paying\n    When I pay 5\n    * a receipt is printed\n")
+      .unwrap();
+
+    assert_eq!(feature.name, "Billing");
+    assert_eq!(feature.description, "In order to pay\nAs a customer");
+    assert_eq!(feature.tags, vec!["@billing".to_owned()]);
+    assert_eq!(feature.tag_lines, vec![2]);
+    assert_eq!(feature.line, 3);
+    assert_eq!(feature.background_line, Some(7));
+    assert_eq!(feature.background,
+               vec![Step {
+                      keyword: "Given".to_owned(),
+                      text: "a customer".to_owned(),
+                      line: 8,
+                      argument: None,
+                    }]);
+    assert_eq!(feature.scenarios,
+               vec![Scenario {
+                      keyword: "Scenario".to_owned(),
+                      name: "Paying".to_owned(),
+                      tags: vec!["@fast".to_owned(), "@smoke".to_owned()],
+                      tag_lines: vec![10, 10],
+                      rule_tags: Vec::new(),
+                      rule_tag_lines: Vec::new(),
+                      rule_background_line: None,
+                      rule_background: Vec::new(),
+                      examples_tags: Vec::new(),
+                      examples_tag_lines: Vec::new(),
+                      line: 11,
+                      outline_line: None,
+                      steps: vec![Step {
+                                    keyword: "When".to_owned(),
+                                    text: "I pay 5".to_owned(),
+                                    line: 12,
+                                    argument: None,
+                                  },
+                                  Step {
+                                    keyword: "*".to_owned(),
+                                    text: "a receipt is printed".to_owned(),
+                                    line: 13,
+                                    argument: None,
+                                  }],
+                    }]);
+    assert_eq!(feature.scenario_tags(&feature.scenarios[0]),
+               vec!["@billing".to_owned(), "@fast".to_owned(), "@smoke".to_owned()]);
+  }
+
+  #[test]
+  fn it_keeps_the_line_of_each_tag() {
+    let feature = parse("test.feature",
+                        "Feature: Tags
+  @slow @db
+  # Needs a database
+
+  @nightly
+                           Scenario: Tagged
+    Given a step
+")
+      .unwrap();
+
+    assert_eq!(feature.scenarios[0].tags,
+               vec!["@slow".to_owned(), "@db".to_owned(), "@nightly".to_owned()]);
+    assert_eq!(feature.scenarios[0].tag_lines, vec![2, 2, 5]);
+  }
+
+  #[test]
+  fn it_parses_step_arguments() {
+    let feature = parse("test.feature",
+                        "Feature: Arguments\n  Scenario: Both\n    Given the text\n      \"\"\"\n      \
+                         Some text\n        indented\n      \"\"\"\n    And the table\n      | a | \
+                         b\\|c |\n      | 1 | 2   |\n")
+      .unwrap();
+    let steps = &feature.scenarios[0].steps;
+
+    assert_eq!(steps[0].argument,
+               Some(StepArgument::DocString("Some text\n  indented".to_owned())));
+    assert_eq!(steps[1].argument,
+               Some(StepArgument::Table(vec![vec!["a".to_owned(), "b|c".to_owned()],
+                                             vec!["1".to_owned(), "2".to_owned()]])));
+  }
+
+  #[test]
+  fn it_dedents_docstrings_by_characters() {
+    let feature = parse("test.feature",
+                        "Feature: Spaces\n  Scenario: Wide\n    Given the text\n      \"\"\"\n     \
+                         \u{a0}wide\n      \u{a0}wider\n      \"\"\"\n")
+      .unwrap();
+
+    assert_eq!(feature.scenarios[0].steps[0].argument,
+               Some(StepArgument::DocString("wide\n\u{a0}wider".to_owned())));
+  }
+
+  #[test]
+  fn it_expands_scenario_outlines() {
+    let feature = parse("test.feature",
+                        "Feature: Outlines\n  @outline\n  Scenario Outline: Eating <n>\n    Given I \
+                         eat <n> cucumbers\n\n    Examples:\n      | n |\n      | 1 |\n\n    \
+                         @more\n    Examples:\n      | n |\n      | 5 |\n")
+      .unwrap();
+
+    assert_eq!(feature.scenarios.len(), 2);
+    assert_eq!(feature.scenarios[0].name, "Eating 1");
+    assert_eq!(feature.scenarios[0].line, 8);
+    assert_eq!(feature.scenarios[0].outline_line, Some(3));
+    assert_eq!(feature.scenarios[0].steps[0].text, "I eat 1 cucumbers");
+    assert_eq!(feature.scenarios[1].steps[0].text, "I eat 5 cucumbers");
+    assert_eq!(feature.scenarios[1].tags, vec!["@outline".to_owned()]);
+    assert_eq!(feature.scenarios[1].examples_tags, vec!["@more".to_owned()]);
+    assert_eq!(feature.scenarios[1].tag_lines, vec![2]);
+    assert_eq!(feature.scenarios[1].examples_tag_lines, vec![10]);
+    assert_eq!(feature.scenario_tags(&feature.scenarios[1]),
+               vec!["@outline".to_owned(), "@more".to_owned()]);
+  }
+
+  #[test]
+  fn it_flattens_rules() {
+    let feature = parse("test.feature",
+                        "@f\nFeature: Rules\n  Scenario: Outside\n    Given a step\n\n  @r\n  \
+                         Rule: Tagged\n    Some description\n\n    @s\n    Example: Inside\n      \
+                         Given a step\n")
+      .unwrap();
+
+    assert_eq!(feature.scenarios.len(), 2);
+    assert_eq!(feature.scenarios[0].rule_tags, Vec::<String>::new());
+    assert_eq!(feature.scenarios[1].rule_tags, vec!["@r".to_owned()]);
+    assert_eq!(feature.scenarios[1].rule_tag_lines, vec![6]);
+    assert_eq!(feature.scenario_tags(&feature.scenarios[1]),
+               vec!["@f".to_owned(), "@r".to_owned(), "@s".to_owned()]);
+  }
+
+  #[test]
+  fn it_keeps_rule_backgrounds_with_their_scenarios() {
+    let feature = parse("test.feature",
+                        "Feature: Rules\n  Background:\n    Given a feature step\n\n  Rule: \
+                         First\n    Background:\n      Given a rule step\n\n    Example: \
+                         Inside\n      Given a step\n\n  Rule: Second\n    Example: Bare\n      \
+                         Given a step\n")
+      .unwrap();
+
+    let inside = &feature.scenarios[0];
+    assert_eq!(inside.rule_background_line, Some(6));
+    assert_eq!(inside.rule_background[0].text, "a rule step");
+    assert_eq!(feature.background_steps(inside)
+                 .iter()
+                 .map(|step| step.text.as_str())
+                 .collect::<Vec<_>>(),
+               vec!["a feature step", "a rule step"]);
+    assert_eq!(feature.scenarios[1].rule_background_line, None);
+    assert_eq!(feature.background_steps(&feature.scenarios[1]).len(), 1);
+    assert_eq!(parse("test.feature",
+                     "Feature: F\n  Rule: R\n    Example: E\n      Given a step\n    \
+                      Background:\n")
+                 .unwrap_err()
+                 .message,
+               "Background must come before any Scenario");
+  }
+
+  #[test]
+  fn it_reports_parse_errors() {
+    assert_eq!(parse("test.feature", "Scenario: Lost\n"),
+               Err(ParseError {
+                 path: "test.feature".to_owned(),
+                 line: 1,
+                 message: "Expected a Feature".to_owned(),
+               }));
+    assert_eq!(parse("test.feature", "Feature: F\n  | a table |\n").unwrap_err().line,
+               2);
+    assert_eq!(parse("test.feature", "Feature: F\n  Examples:\n").unwrap_err().message,
+               "Examples must follow a Scenario Outline");
+  }
+}
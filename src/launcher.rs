@@ -1,9 +1,30 @@
 use server::Server;
 use definitions::registration::CucumberRegistrar;
-use runner::WorldRunner;
+use definitions::listing::{self, ListFormat, StepDefinition};
+use definitions::snippet::SnippetStyle;
+use format::{Format, Formatter};
+use format::json::Json;
+use format::junit::JUnit;
+use format::libtest::LibTest;
+use format::messages::Messages;
+use format::pretty::Pretty;
+use format::rerun::Rerun;
+use format::tap::Tap;
+use gherkin::{self, Feature};
+use native::{NativeRunner, Strictness};
+use recording::{self, RecordingRunner};
+use runner::{self, CommandRunner, WorldRunner};
+use state::Cucumber;
+use tags::TagExpression;
 use itertools::Itertools;
+use regex::Regex;
 
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
 use std::process::{self, Command, Stdio};
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 use std::env;
@@ -18,6 +39,17 @@ pub struct CucumberConfig<'a, W: Send + 'static> {
   addr: &'static str,
   registrar_fns: Vec<&'a Fn(&mut CucumberRegistrar<W>)>,
   args: Vec<&'static str>,
+  log_malformed: bool,
+  record_path: Option<&'a str>,
+  step_timeout: Option<Duration>,
+  snippet_style: SnippetStyle,
+  unused_format: Option<ListFormat>,
+  unused_path: Option<&'a str>,
+  fail_on_unused: bool,
+  report_usage: bool,
+  fail_fast: bool,
+  formats: Vec<(Format, Option<&'a str>)>,
+  listeners: Vec<Box<Formatter>>,
 }
 
 /// Configure the Cucumber server and Ruby client
@@ -57,6 +89,17 @@ pub fn create_config<'a, W: Send + 'static>(world: W) -> CucumberConfig<'a, W> {
     addr: "127.0.0.1:7878",
     registrar_fns: Vec::new(),
     args: Vec::new(),
+    log_malformed: false,
+    record_path: None,
+    step_timeout: None,
+    snippet_style: SnippetStyle::Regex,
+    unused_format: None,
+    unused_path: None,
+    fail_on_unused: false,
+    report_usage: false,
+    fail_fast: false,
+    formats: Vec::new(),
+    listeners: Vec::new(),
   }
 }
 
@@ -95,36 +138,514 @@ impl<'a, W: Send + 'static> CucumberConfig<'a, W> {
     self
   }
 
+  /// Logs the raw text of wire requests that could not be parsed to stderr
+  pub fn log_malformed_requests(mut self, enabled: bool) -> CucumberConfig<'a, W> {
+    self.log_malformed = enabled;
+    self
+  }
+
+  /// Records all wire traffic to a file, which can later be fed to `replay`
+  pub fn record_to(mut self, path: &'a str) -> CucumberConfig<'a, W> {
+    self.record_path = Some(path);
+    self
+  }
+
+  /// Fails any step that runs longer than the timeout
+  ///
+  /// Timeouts given to individual steps in their registration take precedence.
+  pub fn step_timeout(mut self, timeout: Duration) -> CucumberConfig<'a, W> {
+    self.step_timeout = Some(timeout);
+    self
+  }
+
+  /// Chooses whether snippets for undefined steps use regular expressions or
+  /// Cucumber Expressions
+  pub fn snippet_style(mut self, style: SnippetStyle) -> CucumberConfig<'a, W> {
+    self.snippet_style = style;
+    self
+  }
+
+  /// Reports the step definitions that were never matched or invoked, once
+  /// the run is over
+  pub fn report_unused_steps(mut self, format: ListFormat) -> CucumberConfig<'a, W> {
+    self.unused_format = Some(format);
+    self
+  }
+
+  /// Writes the unused step report to a file, instead of stdout
+  pub fn unused_steps_path(mut self, path: &'a str) -> CucumberConfig<'a, W> {
+    self.unused_path = Some(path);
+    self
+  }
+
+  /// Fails an otherwise successful run if any step definition went unused
+  pub fn fail_on_unused_steps(mut self, enabled: bool) -> CucumberConfig<'a, W> {
+    self.fail_on_unused = enabled;
+    self
+  }
+
+  /// Prints how often each step definition was used and how long it took,
+  /// once the run is over
+  pub fn report_step_usage(mut self, enabled: bool) -> CucumberConfig<'a, W> {
+    self.report_usage = enabled;
+    self
+  }
+
+  /// Stops the run after the first failed scenario
+  ///
+  /// Native runs stop as `--fail-fast` makes them, and the ruby client is
+  /// given `--fail-fast`.
+  pub fn fail_fast(mut self, enabled: bool) -> CucumberConfig<'a, W> {
+    self.fail_fast = enabled;
+    self
+  }
+
+  /// Adds a formatter for native runs, writing to stdout
+  ///
+  /// Formatters given on the command line with `--format` replace the
+  /// configured ones. Without any, native runs use the pretty formatter.
+  pub fn format(mut self, format: Format) -> CucumberConfig<'a, W> {
+    self.formats.push((format, None));
+    self
+  }
+
+  /// Adds a formatter for native runs, writing to a file
+  pub fn format_to(mut self, format: Format, path: &'a str) -> CucumberConfig<'a, W> {
+    self.formats.push((format, Some(path)));
+    self
+  }
+
+  /// Adds a listener told about every event of native runs, alongside the
+  /// formatters
+  pub fn listener<L: Formatter + 'static>(mut self, listener: L) -> CucumberConfig<'a, W> {
+    self.listeners.push(Box::new(listener));
+    self
+  }
+
   /// Starts Cucumber and the ruby client using the defined settings.
-  #[allow(unused_variables)]
-  pub fn start(self) {
-    let mut runner = WorldRunner::new(self.world);
+  ///
+  /// When run with `--list-steps` (or `--list-steps=json`), prints the
+  /// registered step definitions instead, without starting the ruby client.
+  pub fn start(mut self) {
+    if self.fail_fast {
+      self.args.push("--fail-fast");
+    }
+
+    let runner = build_runner(self.world,
+                              &self.registrar_fns,
+                              self.step_timeout,
+                              self.snippet_style);
+
+    if let Some(format) = env::args().skip(1).filter_map(|a| ListFormat::from_arg(&a)).next() {
+      print!("{}", listing::list_steps(format, runner.cucumber().definitions()));
+      process::exit(0);
+    }
+
+    let usage = runner.usage();
+    let definitions: Vec<StepDefinition> = runner.cucumber().definitions().collect();
+    // NOTE: The server keeps the runner, and the ruby client has no request for
+    // the end of a run, so AfterAll hooks are run here once it exits
+    let cuke = runner.cucumber().share();
+
+    let mut status = match self.record_path {
+      Some(path) => {
+        let runner = RecordingRunner::create(runner, path)
+          .unwrap_or_else(|e| panic!("failed to create wire recording {}: {}", path, e));
+        serve(runner, self.addr, self.log_malformed, self.args)
+      },
+      None => serve(runner, self.addr, self.log_malformed, self.args),
+    };
+
+    let after_all_failures = runner::run_after_all_hooks(&cuke);
+    for failure in &after_all_failures {
+      let _ = writeln!(io::stderr(), "{}", failure);
+    }
+    if status == 0 && !after_all_failures.is_empty() {
+      status = 1;
+    }
+
+    if self.report_usage {
+      print!("{}", usage.report(definitions.iter().cloned()));
+    }
+
+    let unused = usage.unused(definitions);
+    if self.unused_format.is_some() || self.unused_path.is_some() || self.fail_on_unused {
+      report_unused(self.unused_format.unwrap_or(ListFormat::Text),
+                    self.unused_path,
+                    &unused);
+    }
+
+    if status == 0 && self.fail_on_unused && !unused.is_empty() {
+      process::exit(1);
+    }
+    process::exit(status);
+  }
+
+  /// Replays a session written by `record_to` against the registered steps,
+  /// without starting the ruby client.
+  ///
+  /// Exits with a failure code if any response differs from the recorded one.
+  pub fn replay(self, path: &str) {
+    let mut runner = build_runner(self.world,
+                                  &self.registrar_fns,
+                                  self.step_timeout,
+                                  self.snippet_style);
+
+    let mismatches = recording::replay(&mut runner, path)
+      .unwrap_or_else(|e| panic!("failed to replay wire recording {}: {:?}", path, e));
+
+    mismatches.iter().foreach(|mismatch| {
+      let _ = writeln!(io::stderr(),
+                       "{}:{}: expected [{}] but got [{}]",
+                       path,
+                       mismatch.line,
+                       mismatch.expected,
+                       mismatch.actual);
+    });
 
+    process::exit(if mismatches.is_empty() { 0 } else { 1 });
+  }
+}
+
+impl<'a, W: Clone + Send + 'static> CucumberConfig<'a, W> {
+  /// Runs the features with the registered steps, without the ruby client
+  ///
+  /// Each scenario starts with a clone of the configured world. Feature files
+  /// or directories to run are taken from the command line, defaulting to
+  /// `features`, and `--list-steps` works as it does for `start`. Formatters
+  /// are chosen with `--format <name>`, each optionally followed by
+  /// `--out <path>`, and scenarios with `--tags <expression>`, which must all
+  /// match when given more than once, `--name <regex>`, any of which may
+  /// match, or by giving `path:line` for a feature file. `--rerun <path>` runs
+  /// the scenarios listed by the `rerun` formatter, `--retry <n>` retries
+  /// failed scenarios up to `n` times, and `--fail-fast` stops the run after
+  /// the first scenario that fails. `--strict` fails the run on pending and
+  /// undefined steps, and `--strict-pending` and `--strict-undefined` on just
+  /// one of them. `--dry-run` matches every step and checks its arguments
+  /// without running any step or hook, and `--threads <n>` runs scenarios on
+  /// `n` threads at once, which the formats that report what steps print
+  /// cannot be used with. The ruby client arguments and the wire options are
+  /// ignored.
+  pub fn start_native(self) {
+    let options = NativeOptions::parse(env::args().skip(1))
+      .and_then(|options| options.default_formats(&self.formats))
+      .unwrap_or_else(|message| {
+        let _ = writeln!(io::stderr(), "{}", message);
+        process::exit(2);
+      });
+
+    // NOTE: The world is only cloned from, but threads of a parallel run may
+    // do so at the same time
+    let world = Mutex::new(self.world);
+    let mut runner = NativeRunner::new(move || world.lock().unwrap().clone())
+      .snippet_style(self.snippet_style)
+      .capture_output(options.formats.iter().any(|&(format, _)| format.reports_output()))
+      .retry(options.retry)
+      .fail_fast(options.fail_fast || self.fail_fast)
+      .strict(options.strictness)
+      .dry_run(options.dry_run)
+      .threads(options.threads);
+    if let Some(expression) = options.tags {
+      runner = runner.filter_tags(expression);
+    }
+    for name in &options.names {
+      runner = runner.filter_name(Regex::new(name).unwrap());
+    }
+    for (path, lines) in options.lines {
+      runner = runner.filter_lines(&path, lines);
+    }
     self.registrar_fns.iter().foreach(|fun| fun(&mut runner));
 
-    let server = Server::new(runner);
-    // NOTE: Unused stop_rx needs to be held, or it will drop and close the server
-    let (handle, stop_rx) = server.start(Some(self.addr));
+    if let Some(format) = options.list_steps {
+      print!("{}", listing::list_steps(format, runner.cucumber().definitions()));
+      process::exit(0);
+    }
 
-    let status = ruby_command(self.args)
-      .spawn()
-      .unwrap_or_else(|e| panic!("failed to execute process: {}. Is Cucumber on path?", e))
-      .wait()
-      .unwrap();
+    let features = load_features(&options.paths);
 
-    // NOTE: Join disabled because of edge case when having zero tests
-    //   In that case, ruby cuke will not make tcp connection. It is
-    //   so far impossible to break from tcp::accept, so we must kill
-    // TODO: Investigate MIO to resolve this
-    // handle.join().unwrap();
-    // NOTE: Sleep is an interim solution, to allow the thread time to clean up in
-    // the typical case
-    thread::sleep(Duration::new(2, 0));
+    // Failing steps are reported by the formatter, which the default panic
+    // output would only clutter
+    runner::silence_step_panics();
+    let mut formatters: Vec<Box<Formatter>> = options.formats
+      .iter()
+      .map(|&(format, ref path)| {
+        build_formatter(format, path.as_ref().map(|p| p.as_str()), runner.cucumber())
+      })
+      .collect();
+    formatters.extend(self.listeners);
+    let summary = runner.run(&features, &mut formatters);
 
-    process::exit(status.code().unwrap());
+    process::exit(if summary.passed() { 0 } else { 1 });
   }
 }
 
+/// Command line options of native runs
+#[derive(Debug, PartialEq)]
+struct NativeOptions {
+  paths: Vec<String>,
+  list_steps: Option<ListFormat>,
+  formats: Vec<(Format, Option<String>)>,
+  tags: Option<TagExpression>,
+  /// Regular expressions for the names of scenarios to run
+  names: Vec<String>,
+  /// The lines of scenarios to run in the feature files given as `path:line`
+  lines: Vec<(String, Vec<usize>)>,
+  /// How many times to retry failed scenarios
+  retry: usize,
+  fail_fast: bool,
+  strictness: Strictness,
+  dry_run: bool,
+  /// How many scenarios to run at once
+  threads: usize,
+}
+
+impl NativeOptions {
+  fn parse<I: Iterator<Item = String>>(mut args: I) -> Result<NativeOptions, String> {
+    let mut options = NativeOptions {
+      paths: Vec::new(),
+      list_steps: None,
+      formats: Vec::new(),
+      tags: None,
+      names: Vec::new(),
+      lines: Vec::new(),
+      retry: 0,
+      fail_fast: false,
+      strictness: Strictness::default(),
+      dry_run: false,
+      threads: 1,
+    };
+    // A rerun file with nothing in it means there is nothing to run, rather
+    // than that everything should be
+    let mut rerun = false;
+
+    while let Some(arg) = args.next() {
+      if let Some(format) = ListFormat::from_arg(&arg) {
+        options.list_steps = Some(format);
+      } else if arg == "--format" || arg == "-f" {
+        let name = try!(args.next().ok_or(format!("{} needs a formatter name", arg)));
+        let format = try!(Format::from_name(&name).ok_or(format!("Unknown formatter {}", name)));
+        options.formats.push((format, None));
+      } else if arg == "--out" || arg == "-o" {
+        let path = try!(args.next().ok_or(format!("{} needs a path", arg)));
+        match options.formats.last_mut() {
+          Some(&mut (_, ref mut out)) => *out = Some(path),
+          None => return Err(format!("{} must follow a --format", arg)),
+        }
+      } else if arg == "--tags" || arg == "-t" {
+        let text = try!(args.next().ok_or(format!("{} needs a tag expression", arg)));
+        let expression = try!(TagExpression::parse(&text).map_err(|err| err.to_string()));
+        options.tags = Some(match options.tags.take() {
+          Some(tags) => TagExpression::And(Box::new(tags), Box::new(expression)),
+          None => expression,
+        });
+      } else if arg == "--name" || arg == "-n" {
+        let name = try!(args.next().ok_or(format!("{} needs a regular expression", arg)));
+        try!(Regex::new(&name).map_err(|err| format!("Invalid name {}: {}", name, err)));
+        options.names.push(name);
+      } else if arg == "--fail-fast" {
+        options.fail_fast = true;
+      } else if arg == "--threads" {
+        let count = try!(args.next().ok_or(format!("{} needs a number of threads", arg)));
+        options.threads = match count.parse() {
+          Ok(threads) if threads > 0 => threads,
+          _ => return Err(format!("{} needs a number of threads, not {}", arg, count)),
+        };
+      } else if arg == "--dry-run" {
+        options.dry_run = true;
+      } else if arg == "--strict" {
+        options.strictness = Strictness::strict();
+      } else if arg == "--strict-pending" {
+        options.strictness.pending = true;
+      } else if arg == "--strict-undefined" {
+        options.strictness.undefined = true;
+      } else if arg == "--retry" {
+        let count = try!(args.next().ok_or(format!("{} needs a number of retries", arg)));
+        options.retry = try!(count.parse()
+          .map_err(|_| format!("{} needs a number of retries, not {}", arg, count)));
+      } else if arg == "--rerun" {
+        let path = try!(args.next().ok_or(format!("{} needs a path", arg)));
+        let mut source = String::new();
+        try!(File::open(&path)
+          .and_then(|mut file| file.read_to_string(&mut source))
+          .map_err(|err| format!("Failed to read rerun file {}: {}", path, err)));
+        // NOTE: Paths may have spaces in them, so there is one file per line
+        for entry in source.lines().map(str::trim).filter(|entry| !entry.is_empty()) {
+          options.add_path(entry);
+        }
+        rerun = true;
+      } else if arg.starts_with('-') {
+        return Err(format!("Unknown option {}", arg));
+      } else {
+        options.add_path(&arg);
+      }
+    }
+
+    if options.paths.is_empty() && !rerun {
+      options.paths.push("features".to_owned());
+    }
+    Ok(options)
+  }
+
+  // Falls back to the formats of the config when none were given on the
+  // command line, and to pretty output when it has none either
+  fn default_formats(mut self,
+                     formats: &[(Format, Option<&str>)])
+                     -> Result<NativeOptions, String> {
+    if self.formats.is_empty() {
+      self.formats =
+        formats.iter().map(|&(format, path)| (format, path.map(|p| p.to_owned()))).collect();
+    }
+    if self.formats.is_empty() {
+      self.formats.push((Format::Pretty, None));
+    }
+    // NOTE: Capturing works on the stdout of the whole process, so what steps
+    // print cannot be told apart on several threads
+    if self.threads > 1 && self.formats.iter().any(|&(format, _)| format.reports_output()) {
+      return Err("--threads cannot be used with the junit, json, libtest and libtest-json \
+                  formats, which report what steps print"
+        .to_owned());
+    }
+    Ok(self)
+  }
+
+  // Adds a feature file or directory to run, with the lines to run in it if
+  // given as `path:line`
+  fn add_path(&mut self, arg: &str) {
+    let (path, lines) = split_lines(arg);
+    if !lines.is_empty() {
+      self.lines.push((path.clone(), lines));
+    }
+    // A feature file given with several lines is only run once
+    if !self.paths.contains(&path) {
+      self.paths.push(path);
+    }
+  }
+}
+
+// Splits `path:line:line` into the path and its lines
+fn split_lines(arg: &str) -> (String, Vec<usize>) {
+  let mut parts: Vec<&str> = arg.split(':').collect();
+  let mut lines = Vec::new();
+  while parts.len() > 1 {
+    match parts[parts.len() - 1].parse() {
+      Ok(line) => lines.insert(0, line),
+      Err(_) => break,
+    }
+    parts.pop();
+  }
+  (parts.join(":"), lines)
+}
+
+fn build_formatter<W>(format: Format, path: Option<&str>, cuke: &Cucumber<W>) -> Box<Formatter> {
+  let file = path.map(|path| {
+    File::create(path).unwrap_or_else(|e| {
+      let _ = writeln!(io::stderr(), "Failed to create {}: {}", path, e);
+      process::exit(2);
+    })
+  });
+
+  match (format, file) {
+    (Format::Pretty, None) => Box::new(Pretty::stdout()),
+    (Format::Pretty, Some(file)) => Box::new(Pretty::new(file, false)),
+    (Format::JUnit, None) => Box::new(JUnit::new(io::stdout())),
+    (Format::JUnit, Some(file)) => Box::new(JUnit::new(file)),
+    (Format::Json, None) => Box::new(Json::new(io::stdout())),
+    (Format::Json, Some(file)) => Box::new(Json::new(file)),
+    (Format::Messages, None) => Box::new(Messages::new(io::stdout(), cuke.definitions())),
+    (Format::Messages, Some(file)) => Box::new(Messages::new(file, cuke.definitions())),
+    (Format::Tap, None) => Box::new(Tap::new(io::stdout())),
+    (Format::Tap, Some(file)) => Box::new(Tap::new(file)),
+    (Format::LibTest, None) => Box::new(LibTest::new(io::stdout())),
+    (Format::LibTest, Some(file)) => Box::new(LibTest::new(file)),
+    (Format::LibTestJson, None) => Box::new(LibTest::json(io::stdout())),
+    (Format::LibTestJson, Some(file)) => Box::new(LibTest::json(file)),
+    (Format::Rerun, None) => Box::new(Rerun::new(io::stdout())),
+    (Format::Rerun, Some(file)) => Box::new(Rerun::new(file)),
+  }
+}
+
+// Loads the features of files and directories, once each even if a file is
+// also in one of the directories
+fn load_features(paths: &[String]) -> Vec<Feature> {
+  let mut features = Vec::new();
+  let mut loaded = HashSet::new();
+  for path in paths {
+    match gherkin::read_features(path) {
+      Ok(found) => {
+        features.extend(found.into_iter().filter(|feature| {
+          loaded.insert(fs::canonicalize(&feature.path)
+            .unwrap_or_else(|_| PathBuf::from(&feature.path)))
+        }))
+      },
+      Err(err) => {
+        let _ = writeln!(io::stderr(), "Failed to load features: {}", err);
+        process::exit(2);
+      },
+    }
+  }
+  features
+}
+
+fn build_runner<W: Send + 'static>(world: W,
+                                   registrar_fns: &[&Fn(&mut CucumberRegistrar<W>)],
+                                   step_timeout: Option<Duration>,
+                                   snippet_style: SnippetStyle)
+                                   -> WorldRunner<W> {
+  let mut runner = WorldRunner::new(world).snippet_style(snippet_style);
+
+  if let Some(timeout) = step_timeout {
+    runner = runner.step_timeout(timeout);
+  }
+
+  registrar_fns.iter().foreach(|fun| fun(&mut runner));
+  runner
+}
+
+fn report_unused(format: ListFormat, path: Option<&str>, unused: &[StepDefinition]) {
+  let report = listing::list_steps(format, unused.iter().cloned());
+
+  match path {
+    Some(path) => {
+      File::create(path)
+        .and_then(|mut file| file.write_all(report.as_bytes()))
+        .unwrap_or_else(|e| panic!("failed to write unused step report {}: {}", path, e))
+    },
+    None if format == ListFormat::Text && !unused.is_empty() => {
+      print!("{} unused step definitions:\n{}", unused.len(), report)
+    },
+    None if format == ListFormat::Text => {},
+    None => print!("{}", report),
+  }
+}
+
+#[allow(unused_variables)]
+fn serve<R: CommandRunner + Send + 'static>(runner: R,
+                                            addr: &'static str,
+                                            log_malformed: bool,
+                                            args: Vec<&'static str>)
+                                            -> i32 {
+  let server = Server::new(runner).log_malformed(log_malformed);
+  // NOTE: Unused stop_rx needs to be held, or it will drop and close the server
+  let (handle, stop_rx) = server.start(Some(addr));
+
+  let status = ruby_command(args)
+    .spawn()
+    .unwrap_or_else(|e| panic!("failed to execute process: {}. Is Cucumber on path?", e))
+    .wait()
+    .unwrap();
+
+  // NOTE: Join disabled because of edge case when having zero tests
+  //   In that case, ruby cuke will not make tcp connection. It is
+  //   so far impossible to break from tcp::accept, so we must kill
+  // TODO: Investigate MIO to resolve this
+  // handle.join().unwrap();
+  // NOTE: Sleep is an interim solution, to allow the thread time to clean up in
+  // the typical case
+  thread::sleep(Duration::new(2, 0));
+
+  status.code().unwrap()
+}
+
 
 /// Build a command to execute the Ruby Cucumber Server.
 /// Takes a list of extra command line arguments to the server
@@ -146,3 +667,188 @@ pub fn ruby_command(args: Vec<&'static str>) -> Command {
   command.args(args.as_slice());
   command
 }
+
+#[cfg(test)]
+mod test {
+  use super::{load_features, NativeOptions};
+
+  use std::fs::File;
+  use std::io::Write;
+
+  use tempdir::TempDir;
+
+  use definitions::listing::ListFormat;
+  use format::Format;
+  use native::Strictness;
+  use tags::TagExpression;
+
+  fn parse(args: &[&str]) -> Result<NativeOptions, String> {
+    NativeOptions::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  #[test]
+  fn it_parses_native_options() {
+    assert_eq!(parse(&[]),
+               Ok(NativeOptions {
+                 paths: vec!["features".to_owned()],
+                 list_steps: None,
+                 formats: Vec::new(),
+                 tags: None,
+                 names: Vec::new(),
+                 lines: Vec::new(),
+                 retry: 0,
+                 fail_fast: false,
+                 strictness: Strictness::default(),
+                 dry_run: false,
+                 threads: 1,
+               }));
+    assert_eq!(parse(&["features/a.feature", "--list-steps=json", "other"]),
+               Ok(NativeOptions {
+                 paths: vec!["features/a.feature".to_owned(), "other".to_owned()],
+                 list_steps: Some(ListFormat::Json),
+                 formats: Vec::new(),
+                 tags: None,
+                 names: Vec::new(),
+                 lines: Vec::new(),
+                 retry: 0,
+                 fail_fast: false,
+                 strictness: Strictness::default(),
+                 dry_run: false,
+                 threads: 1,
+               }));
+    assert_eq!(parse(&["--bogus"]), Err("Unknown option --bogus".to_owned()));
+  }
+
+  #[test]
+  fn it_parses_formatters() {
+    assert_eq!(parse(&["--format", "pretty", "-f", "junit", "--out", "report.xml"])
+                 .unwrap()
+                 .formats,
+               vec![(Format::Pretty, None), (Format::JUnit, Some("report.xml".to_owned()))]);
+    assert_eq!(parse(&["--format", "html"]), Err("Unknown formatter html".to_owned()));
+    assert_eq!(parse(&["--out", "report.xml"]),
+               Err("--out must follow a --format".to_owned()));
+  }
+
+  #[test]
+  fn it_parses_scenario_selections() {
+    let options = parse(&["features/a.feature:12:30",
+                          "-n",
+                          "login.*expired",
+                          "features",
+                          "features/a.feature:2"])
+      .unwrap();
+    // NOTE: A file in a directory that is also given is only loaded once, by
+    // load_features
+    assert_eq!(options.paths, vec!["features/a.feature".to_owned(), "features".to_owned()]);
+    assert_eq!(options.lines,
+               vec![("features/a.feature".to_owned(), vec![12, 30]),
+                    ("features/a.feature".to_owned(), vec![2])]);
+    assert_eq!(options.names, vec!["login.*expired".to_owned()]);
+    assert!(parse(&["--name", "(unclosed"]).unwrap_err().starts_with("Invalid name (unclosed"));
+  }
+
+  #[test]
+  fn it_loads_features_once() {
+    let dir = TempDir::new("cucumber_load").unwrap();
+    for name in &["a", "b"] {
+      File::create(dir.path().join(format!("{}.feature", name)))
+        .and_then(|mut file| write!(file, "Feature: {}\n  Scenario: {}\n", name, name))
+        .unwrap();
+    }
+    let file = dir.path().join("a.feature").to_string_lossy().into_owned();
+
+    let features = load_features(&[file, dir.path().to_string_lossy().into_owned()]);
+    let names: Vec<&str> = features.iter().map(|feature| feature.name.as_str()).collect();
+    assert_eq!(names, vec!["a", "b"]);
+  }
+
+  #[test]
+  fn it_reads_rerun_files() {
+    let dir = TempDir::new("cucumber_rerun").unwrap();
+    let path = dir.path().join("rerun.txt");
+    File::create(&path)
+      .and_then(|mut file| file.write_all(b"features/a.feature:4:11\nfeatures/my c.feature:4\n"))
+      .unwrap();
+    let path = path.to_string_lossy().into_owned();
+
+    let options = parse(&["--rerun", &path]).unwrap();
+    assert_eq!(options.paths,
+               vec!["features/a.feature".to_owned(), "features/my c.feature".to_owned()]);
+    assert_eq!(options.lines,
+               vec![("features/a.feature".to_owned(), vec![4, 11]),
+                    ("features/my c.feature".to_owned(), vec![4])]);
+
+    File::create(&path).unwrap();
+    assert_eq!(parse(&["--rerun", &path]).unwrap().paths, Vec::<String>::new());
+    assert!(parse(&["--rerun", "no/such/rerun.txt"])
+      .unwrap_err()
+      .starts_with("Failed to read rerun file no/such/rerun.txt"));
+  }
+
+  #[test]
+  fn it_parses_fail_fast() {
+    assert!(parse(&["--fail-fast", "features"]).unwrap().fail_fast);
+    assert!(!parse(&["features"]).unwrap().fail_fast);
+  }
+
+  #[test]
+  fn it_parses_threads() {
+    assert_eq!(parse(&["--threads", "4"]).unwrap().threads, 4);
+    assert_eq!(parse(&[]).unwrap().threads, 1);
+    assert_eq!(parse(&["--threads", "0"]),
+               Err("--threads needs a number of threads, not 0".to_owned()));
+  }
+
+  #[test]
+  fn it_rejects_threads_with_formats_that_report_output() {
+    let with_config = |args: &[&str], formats: &[(Format, Option<&str>)]| {
+      parse(args).and_then(|options| options.default_formats(formats))
+    };
+    assert!(with_config(&["--threads", "4", "--format", "tap"], &[]).is_ok());
+    assert!(with_config(&["--threads", "4"], &[]).is_ok());
+    assert!(with_config(&["--threads", "4", "--format", "junit"], &[])
+      .unwrap_err()
+      .starts_with("--threads cannot be used with the junit"));
+    assert!(with_config(&["--threads", "4"], &[(Format::Json, Some("report.json"))])
+      .unwrap_err()
+      .starts_with("--threads cannot be used with the junit"));
+    // NOTE: Formats given on the command line replace those of the config
+    assert!(with_config(&["--threads", "4", "--format", "tap"], &[(Format::JUnit, None)]).is_ok());
+    assert!(with_config(&["--format", "junit"], &[]).is_ok());
+  }
+
+  #[test]
+  fn it_parses_dry_runs() {
+    assert!(parse(&["--dry-run"]).unwrap().dry_run);
+    assert!(!parse(&[]).unwrap().dry_run);
+  }
+
+  #[test]
+  fn it_parses_strictness() {
+    assert_eq!(parse(&["--strict"]).unwrap().strictness, Strictness::strict());
+    assert_eq!(parse(&["--strict-pending"]).unwrap().strictness,
+               Strictness {
+                 pending: true,
+                 undefined: false,
+               });
+    assert_eq!(parse(&["--strict-undefined", "--strict-pending"]).unwrap().strictness,
+               Strictness::strict());
+    assert_eq!(parse(&[]).unwrap().strictness, Strictness::default());
+  }
+
+  #[test]
+  fn it_parses_retries() {
+    assert_eq!(parse(&["--retry", "3"]).unwrap().retry, 3);
+    assert_eq!(parse(&["--retry", "often"]),
+               Err("--retry needs a number of retries, not often".to_owned()));
+  }
+
+  #[test]
+  fn it_parses_tag_expressions() {
+    assert_eq!(parse(&["--tags", "@smoke or @fast", "-t", "not @wip"]).unwrap().tags,
+               Some(TagExpression::parse("(@smoke or @fast) and not @wip").unwrap()));
+    assert_eq!(parse(&["--tags", "@smoke and"]),
+               Err("Invalid tag expression \"@smoke and\": Expected a tag at the end".to_owned()));
+  }
+}
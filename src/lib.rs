@@ -7,6 +7,13 @@ extern crate regex;
 extern crate itertools;
 extern crate serde;
 extern crate serde_json;
+extern crate atty;
+extern crate libc;
+#[macro_use]
+extern crate lazy_static;
+
+#[cfg(test)]
+extern crate tempdir;
 
 /// Low level location of step functions and matcher logic
 pub mod state;
@@ -27,14 +34,44 @@ pub mod event;
 /// Helpers for regular expressions
 pub mod cucumber_regex;
 
+/// A Cucumber Wire Protocol client, for talking to [servers](server/index.html)
+pub mod client;
+
+/// Recording and replay of [runner](runner/index.html) traffic
+pub mod recording;
+
+/// Tracking of how step definitions are used during a run
+pub mod usage;
+
+/// Parsing of Gherkin `.feature` files
+pub mod gherkin;
+
+/// Selection of scenarios and hooks by their tags
+pub mod tags;
+
+/// Running features without the Ruby Cucumber client
+pub mod native;
+
+/// Output for [native](native/index.html) runs
+pub mod format;
+
+mod capture;
+
 mod launcher;
 
 pub use launcher::{ruby_command, create_config, CucumberConfig};
 
-pub use runner::{CommandRunner, WorldRunner};
+pub use runner::{CommandRunner, RunnerError, WorldRunner};
 pub use definitions::registration::CucumberRegistrar;
+pub use definitions::snippet::SnippetStyle;
+pub use definitions::listing::ListFormat;
 pub use state::{Cucumber, SendableStep};
 pub use server::Server;
+pub use recording::RecordingRunner;
+pub use client::WireClient;
+pub use usage::StepUsage;
+pub use native::NativeRunner;
+pub use format::Format;
 pub use event::request::InvokeArgument;
 
 /// Destructure a vector of
@@ -94,6 +131,29 @@ macro_rules! try_destructure {
   })
 }
 
+// The body of the step macros, which registers a step destructuring its
+// arguments for the body
+#[doc(hidden)]
+#[macro_export]
+macro_rules! cucumber_step {
+  ($cuke:expr, $keyword:ident, $regex:expr, $timeout:expr, $body:expr) => {{
+    use $crate::cucumber_regex;
+    use $crate::definitions::registration::StepKeyword;
+    $cuke.register_step(StepKeyword::$keyword,
+                        file!(),
+                        line!(),
+                        cucumber_regex::build($regex),
+                        $timeout,
+                        Box::new(move |cuke, world, args| {
+      let args = try_destructure!(args);
+      // NOTE: Dry runs only check that the arguments fit the step
+      if !cuke.dry_run() {
+        ($body)(cuke, world, args)
+      }
+    }))
+  }}
+}
+
 /// Add a Given step to a
 /// [CucumberRegistrar](definitions/registration/trait.CucumberRegistrar.html)
 ///
@@ -117,14 +177,37 @@ macro_rules! try_destructure {
 /// }
 /// ```
 ///
+/// A step may also be given a timeout, after which it is failed. This form is
+/// available for all of the step macros.
+///
+/// ```
+/// #[macro_use]
+/// extern crate cucumber;
+///
+/// use std::time::Duration;
+/// use cucumber::{
+///   CucumberRegistrar,
+///   Cucumber
+/// };
+///
+/// pub fn main () {
+///   let mut cucumber: Cucumber<u32> = Cucumber::new();
+///
+/// Given!(cucumber, "^the coins are counted$", timeout = Duration::from_secs(5),
+/// |_, world: &mut u32, ()| {
+///     *world = 0;
+///   });
+/// }
+/// ```
+///
 #[macro_export]
 macro_rules! Given {
-  ($cuke:expr, $regex:expr, $body:expr) => {{
-    use $crate::cucumber_regex;
-    $cuke.given(file!(), line!(), cucumber_regex::build($regex), Box::new(move |cuke, world, args| {
-      ($body)(cuke, world, try_destructure!(args))
-    }))
-  }}
+  ($cuke:expr, $regex:expr, timeout = $timeout:expr, $body:expr) => {
+    cucumber_step!($cuke, Given, $regex, Some($timeout), $body)
+  };
+  ($cuke:expr, $regex:expr, $body:expr) => {
+    cucumber_step!($cuke, Given, $regex, None, $body)
+  }
 }
 
 /// Add a When step to a
@@ -156,12 +239,12 @@ macro_rules! Given {
 ///
 #[macro_export]
 macro_rules! When {
-  ($cuke:expr, $regex:expr, $body:expr) => {{
-    use $crate::cucumber_regex;
-    $cuke.when(file!(), line!(), cucumber_regex::build($regex), Box::new(move |cuke, world, args| {
-      ($body)(cuke, world, try_destructure!(args))
-    }))
-  }}
+  ($cuke:expr, $regex:expr, timeout = $timeout:expr, $body:expr) => {
+    cucumber_step!($cuke, When, $regex, Some($timeout), $body)
+  };
+  ($cuke:expr, $regex:expr, $body:expr) => {
+    cucumber_step!($cuke, When, $regex, None, $body)
+  }
 }
 
 /// Add a Then step to a
@@ -189,10 +272,130 @@ macro_rules! When {
 ///
 #[macro_export]
 macro_rules! Then {
-  ($cuke:expr, $regex:expr, $body:expr) => {{
-    use $crate::cucumber_regex;
-    $cuke.then(file!(), line!(), cucumber_regex::build($regex), Box::new(move |cuke, world, args| {
-      ($body)(cuke, world, try_destructure!(args))
-    }))
+  ($cuke:expr, $regex:expr, timeout = $timeout:expr, $body:expr) => {
+    cucumber_step!($cuke, Then, $regex, Some($timeout), $body)
+  };
+  ($cuke:expr, $regex:expr, $body:expr) => {
+    cucumber_step!($cuke, Then, $regex, None, $body)
+  }
+}
+
+/// Add a hook run before every scenario, or those matching a tag expression,
+/// to a
+/// [CucumberRegistrar](definitions/registration/trait.CucumberRegistrar.html)
+///
+/// # Example
+/// ```
+/// #[macro_use]
+/// extern crate cucumber;
+///
+/// use cucumber::{
+///   CucumberRegistrar,
+///   Cucumber
+/// };
+///
+/// pub fn main () {
+///   let mut cucumber: Cucumber<u32> = Cucumber::new();
+///
+///   Before!(cucumber, |_, world: &mut u32| {
+///     *world = 10
+///   });
+///
+///   // Only for scenarios tagged @slow
+///   Before!(cucumber, "@slow", |_, world: &mut u32| {
+///     *world = 100
+///   });
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! Before {
+  ($cuke:expr, $tags:expr, $body:expr) => {{
+    use $crate::tags::TagExpression;
+    $cuke.before(file!(),
+                 line!(),
+                 Some(TagExpression::parse($tags).unwrap_or_else(|e| panic!("{}", e))),
+                 Box::new(move |cuke, world| ($body)(cuke, world)))
+  }};
+  ($cuke:expr, $body:expr) => {{
+    $cuke.before(file!(), line!(), None, Box::new(move |cuke, world| ($body)(cuke, world)))
+  }}
+}
+
+/// Add a hook run after every scenario, or those matching a tag expression, to
+/// a
+/// [CucumberRegistrar](definitions/registration/trait.CucumberRegistrar.html)
+///
+/// # Example
+/// ```
+/// #[macro_use]
+/// extern crate cucumber;
+///
+/// use cucumber::{
+///   CucumberRegistrar,
+///   Cucumber
+/// };
+///
+/// pub fn main () {
+///   let mut cucumber: Cucumber<u32> = Cucumber::new();
+///
+///   After!(cucumber, |_, world: &mut u32| {
+///     assert!(*world < 100)
+///   });
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! After {
+  ($cuke:expr, $tags:expr, $body:expr) => {{
+    use $crate::tags::TagExpression;
+    $cuke.after(file!(),
+                line!(),
+                Some(TagExpression::parse($tags).unwrap_or_else(|e| panic!("{}", e))),
+                Box::new(move |cuke, world| ($body)(cuke, world)))
+  }};
+  ($cuke:expr, $body:expr) => {{
+    $cuke.after(file!(), line!(), None, Box::new(move |cuke, world| ($body)(cuke, world)))
+  }}
+}
+
+/// Add a hook run once after the last scenario of a run, even one stopped by
+/// fail fast, to a
+/// [CucumberRegistrar](definitions/registration/trait.CucumberRegistrar.html)
+///
+/// A failing AfterAll hook fails the run. In wire runs the hooks run once the
+/// ruby client exits.
+///
+/// # Example
+/// ```
+/// #[macro_use]
+/// extern crate cucumber;
+///
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicBool, Ordering};
+///
+/// use cucumber::{gherkin, CucumberRegistrar, NativeRunner};
+/// use cucumber::format::pretty::Pretty;
+///
+/// pub fn main () {
+///   let cleaned_up = Arc::new(AtomicBool::new(false));
+///   let flag = cleaned_up.clone();
+///   let mut runner = NativeRunner::new(|| 0u32).fail_fast(true);
+///
+///   AfterAll!(runner, move |_| {
+///     flag.store(true, Ordering::SeqCst)
+///   });
+///
+///   let feature = gherkin::parse("f.feature", "Feature: F\n  Scenario: S\n    Given nothing\n")
+///     .unwrap();
+///   runner.run(&[feature], &mut Pretty::new(Vec::new(), false));
+///   assert!(cleaned_up.load(Ordering::SeqCst));
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! AfterAll {
+  ($cuke:expr, $body:expr) => {{
+    $cuke.after_all(file!(), line!(), $crate::definitions::registration::run_hook($body))
   }}
 }
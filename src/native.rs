@@ -0,0 +1,1726 @@
+use std::collections::BTreeMap;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::channel;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libc;
+use regex::Regex;
+
+use capture::{self, Capture};
+use definitions::registration::{CucumberRegistrar, Hook, HookKind, RunHook, SimpleStep};
+use definitions::snippet::{self, SnippetStyle};
+use event::request::InvokeArgument;
+use event::response::{FailMessage, InvokeResponse, Step as MatchedStep};
+use format::Formatter;
+use gherkin::{Feature, Scenario, Step, StepArgument};
+use runner::{catch_to_response, invoke_to_response, run_after_all_hooks};
+use state::{Cucumber, StepId};
+use tags::{TagExpression, Tags};
+
+/// The outcome of a step, or of a scenario as a whole
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StepStatus {
+  Passed,
+  Failed,
+  Pending,
+  Undefined,
+  /// The step matched more than one step definition
+  Ambiguous,
+  /// The step was not run, because an earlier step did not pass
+  Skipped,
+}
+
+impl StepStatus {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      &StepStatus::Passed => "passed",
+      &StepStatus::Failed => "failed",
+      &StepStatus::Pending => "pending",
+      &StepStatus::Undefined => "undefined",
+      &StepStatus::Ambiguous => "ambiguous",
+      &StepStatus::Skipped => "skipped",
+    }
+  }
+}
+
+/// The result of running a single step
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepResult {
+  pub status: StepStatus,
+  /// The step definition the step matched, if exactly one did
+  pub matched: Option<MatchedStep>,
+  /// The response of the step definition, if it was invoked, or the failure
+  /// of an ambiguous step
+  pub response: Option<InvokeResponse>,
+  /// A snippet implementing the step, if it is undefined
+  pub snippet: Option<String>,
+  /// What the step printed to stdout, if output was captured
+  pub output: String,
+  pub duration: Duration,
+}
+
+impl StepResult {
+  /// The failure message of a failed or ambiguous step
+  pub fn failure(&self) -> Option<&FailMessage> {
+    match self.response {
+      Some(InvokeResponse::Fail(ref message)) => Some(message),
+      _ => None,
+    }
+  }
+
+  /// The message given by a pending step
+  pub fn pending_message(&self) -> Option<&str> {
+    match self.response {
+      Some(InvokeResponse::Pending(ref message)) => Some(message),
+      _ => None,
+    }
+  }
+}
+
+/// The result of running a Before or After hook
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HookResult {
+  pub kind: HookKind,
+  /// Where the hook was defined, as `file:line`
+  pub source: String,
+  pub status: StepStatus,
+  pub response: InvokeResponse,
+  pub duration: Duration,
+}
+
+impl HookResult {
+  /// The failure message of a failed hook
+  pub fn failure(&self) -> Option<&FailMessage> {
+    match self.response {
+      InvokeResponse::Fail(ref message) => Some(message),
+      _ => None,
+    }
+  }
+}
+
+/// The result of running a scenario, background steps included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScenarioResult {
+  /// The status of the first hook or step that did not pass, if any
+  pub status: StepStatus,
+  /// The results of the background steps, followed by those of the scenario
+  pub steps: Vec<StepResult>,
+  /// The results of the Before hooks, followed by those of the After hooks
+  pub hooks: Vec<HookResult>,
+  pub duration: Duration,
+  /// Which run of the scenario this is, starting from 1 and counting up as a
+  /// failed scenario is retried
+  pub attempt: usize,
+  /// Whether the scenario failed and will be run again
+  pub retried: bool,
+}
+
+impl ScenarioResult {
+  /// Whether the scenario passed, but only after being retried
+  pub fn flaky(&self) -> bool {
+    self.status == StepStatus::Passed && self.attempt > 1
+  }
+}
+
+/// Tally of the statuses of steps or scenarios
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct StatusCounts {
+  pub passed: usize,
+  pub failed: usize,
+  pub pending: usize,
+  pub undefined: usize,
+  pub ambiguous: usize,
+  pub skipped: usize,
+}
+
+impl StatusCounts {
+  pub fn add(&mut self, status: StepStatus) {
+    match status {
+      StepStatus::Passed => self.passed += 1,
+      StepStatus::Failed => self.failed += 1,
+      StepStatus::Pending => self.pending += 1,
+      StepStatus::Undefined => self.undefined += 1,
+      StepStatus::Ambiguous => self.ambiguous += 1,
+      StepStatus::Skipped => self.skipped += 1,
+    }
+  }
+
+  pub fn get(&self, status: StepStatus) -> usize {
+    match status {
+      StepStatus::Passed => self.passed,
+      StepStatus::Failed => self.failed,
+      StepStatus::Pending => self.pending,
+      StepStatus::Undefined => self.undefined,
+      StepStatus::Ambiguous => self.ambiguous,
+      StepStatus::Skipped => self.skipped,
+    }
+  }
+
+  pub fn total(&self) -> usize {
+    self.passed + self.failed + self.pending + self.undefined + self.ambiguous + self.skipped
+  }
+}
+
+/// Which scenarios besides failed and ambiguous ones make a run fail
+///
+/// By default, pending and undefined steps leave a run passing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Strictness {
+  pub pending: bool,
+  pub undefined: bool,
+}
+
+impl Strictness {
+  /// Fail runs on pending and undefined steps alike
+  pub fn strict() -> Strictness {
+    Strictness {
+      pending: true,
+      undefined: true,
+    }
+  }
+
+  /// Whether a scenario with the status makes the run fail
+  pub fn fails(&self, status: StepStatus) -> bool {
+    match status {
+      StepStatus::Failed | StepStatus::Ambiguous => true,
+      StepStatus::Pending => self.pending,
+      StepStatus::Undefined => self.undefined,
+      StepStatus::Passed | StepStatus::Skipped => false,
+    }
+  }
+}
+
+/// The totals of a native run
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Summary {
+  /// The statuses of scenarios, as of their last attempt
+  pub scenarios: StatusCounts,
+  /// The statuses of the steps of the last attempt of each scenario
+  pub steps: StatusCounts,
+  /// How many of the passed scenarios only passed after being retried
+  pub flaky: usize,
+  /// What the run was strict about
+  pub strictness: Strictness,
+  pub duration: Duration,
+  /// The messages of the AfterAll hooks that failed
+  pub after_all_failures: Vec<String>,
+}
+
+impl Summary {
+  /// Whether the run passed, that is no scenario failed or was ambiguous, nor
+  /// pending or undefined if the run was strict about it, and no AfterAll hook
+  /// failed
+  pub fn passed(&self) -> bool {
+    let statuses = [StepStatus::Failed,
+                    StepStatus::Ambiguous,
+                    StepStatus::Pending,
+                    StepStatus::Undefined];
+    let scenarios_passed = statuses.iter()
+      .all(|&status| !self.strictness.fails(status) || self.scenarios.get(status) == 0);
+    scenarios_passed && self.after_all_failures.is_empty()
+  }
+
+  // Counts the last attempt of a scenario
+  fn add(&mut self, result: &ScenarioResult) {
+    self.scenarios.add(result.status);
+    if result.flaky() {
+      self.flaky += 1;
+    }
+    for step in &result.steps {
+      self.steps.add(step.status);
+    }
+  }
+}
+
+/// Runs parsed [features](../gherkin/struct.Feature.html) against registered
+/// steps, without the Ruby Cucumber client
+///
+/// Every scenario is run with a fresh World, made by the function the runner
+/// was created with, and between the registered Before and After hooks.
+/// Results are reported as they happen to a
+/// [Formatter](../format/trait.Formatter.html). Step timeouts registered with
+/// the step macros only apply to wire runs.
+///
+/// # Example
+///
+/// ```
+/// #[macro_use]
+/// extern crate cucumber;
+///
+/// use cucumber::{gherkin, CucumberRegistrar, NativeRunner};
+/// use cucumber::format::pretty::Pretty;
+///
+/// fn main() {
+///   let mut runner = NativeRunner::new(|| 0u32);
+///   Given!(runner, "^I have (\\d+) coins$", |_, world: &mut u32, (coins,): (u32,)| {
+///     *world = coins;
+///   });
+///
+///   let feature = gherkin::parse("coins.feature",
+///                                "Feature: Coins\n  Scenario: Rich\n    Given I have 5 coins\n")
+///     .unwrap();
+///   let summary = runner.run(&[feature], &mut Pretty::new(Vec::new(), false));
+///   assert!(summary.passed());
+/// }
+/// ```
+pub struct NativeRunner<World> {
+  cuke: Cucumber<World>,
+  new_world: Arc<Fn() -> World + Send + Sync>,
+  snippet_style: SnippetStyle,
+  // The file descriptor to capture what steps print from, if any
+  captured_fd: Option<libc::c_int>,
+  tags: Option<TagExpression>,
+  names: Vec<Regex>,
+  lines: Vec<(String, Vec<usize>)>,
+  retries: usize,
+  fail_fast: bool,
+  strictness: Strictness,
+  dry_run: bool,
+  threads: usize,
+}
+
+impl<World> NativeRunner<World> {
+  pub fn new<F: Fn() -> World + Send + Sync + 'static>(new_world: F) -> NativeRunner<World> {
+    NativeRunner {
+      cuke: Cucumber::new(),
+      new_world: Arc::new(new_world),
+      snippet_style: SnippetStyle::Regex,
+      captured_fd: None,
+      tags: None,
+      names: Vec::new(),
+      lines: Vec::new(),
+      retries: 0,
+      fail_fast: false,
+      strictness: Strictness::default(),
+      dry_run: false,
+      threads: 1,
+    }
+  }
+
+  /// Choose how snippets for undefined steps describe the step
+  pub fn snippet_style(mut self, style: SnippetStyle) -> NativeRunner<World> {
+    self.snippet_style = style;
+    self
+  }
+
+  /// Capture what steps print to stdout into their results, rather than
+  /// letting it through
+  ///
+  /// Capturing works on the process' stdout as a whole, and is only supported
+  /// on unix. It is left off when running on several
+  /// [threads](#method.threads).
+  pub fn capture_output(mut self, enabled: bool) -> NativeRunner<World> {
+    self.captured_fd = if enabled { Some(capture::STDOUT) } else { None };
+    self
+  }
+
+  /// Only run the scenarios whose tags, including those of their feature,
+  /// match the expression
+  pub fn filter_tags(mut self, expression: TagExpression) -> NativeRunner<World> {
+    self.tags = Some(expression);
+    self
+  }
+
+  /// Only run the scenarios whose name matches one of the regular
+  /// expressions given to this
+  pub fn filter_name(mut self, regex: Regex) -> NativeRunner<World> {
+    self.names.push(regex);
+    self
+  }
+
+  /// Only run the scenarios of the feature file at the path that are at one of
+  /// the lines, leaving other feature files alone
+  ///
+  /// A scenario is at the line of its keyword and the lines of its steps. The
+  /// line of an outline selects all of its rows, and the line of a row of
+  /// examples just that row. The line of the feature selects all of its
+  /// scenarios.
+  pub fn filter_lines(mut self, path: &str, lines: Vec<usize>) -> NativeRunner<World> {
+    self.lines.push((path.to_owned(), lines));
+    self
+  }
+
+  /// Run a failed scenario again, with a fresh World, up to this many more
+  /// times until it passes
+  ///
+  /// A scenario tagged `@retry(n)`, or whose feature, rule or examples are,
+  /// is retried up to `n` times instead. Every attempt is reported to the
+  /// formatter, and a scenario that passes on a later attempt is counted as
+  /// passed and flaky.
+  pub fn retry(mut self, retries: usize) -> NativeRunner<World> {
+    self.retries = retries;
+    self
+  }
+
+  /// Stop the run after the first scenario that fails, without running the
+  /// scenarios after it
+  ///
+  /// The formatter is still told that the feature and the run finished. A
+  /// scenario that passes when retried does not stop the run.
+  pub fn fail_fast(mut self, enabled: bool) -> NativeRunner<World> {
+    self.fail_fast = enabled;
+    self
+  }
+
+  /// Choose whether pending and undefined steps make the run fail, as failed
+  /// steps do
+  ///
+  /// This decides whether the [Summary](struct.Summary.html) of the run
+  /// passed, and so when `fail_fast` stops the run.
+  pub fn strict(mut self, strictness: Strictness) -> NativeRunner<World> {
+    self.strictness = strictness;
+    self
+  }
+
+  /// Match every step without running any hooks or step bodies, to find
+  /// undefined and ambiguous steps and arguments that do not fit their step
+  ///
+  /// Steps added with the step macros convert their arguments as usual, and a
+  /// step whose arguments are of the wrong number or do not parse fails. The
+  /// other steps that match are skipped, and no scenario is retried. Steps
+  /// added without the macros are never invoked, and are skipped as well.
+  ///
+  /// # Example
+  ///
+  /// ```
+  /// #[macro_use]
+  /// extern crate cucumber;
+  ///
+  /// extern crate regex;
+  ///
+  /// use cucumber::{gherkin, CucumberRegistrar, NativeRunner};
+  /// use cucumber::format::pretty::Pretty;
+  /// use regex::Regex;
+  ///
+  /// fn main() {
+  ///   let mut runner = NativeRunner::new(|| 0u32).dry_run(true);
+  ///   Given!(runner, "^I have (\\d+) coins$", |_, _: &mut u32, (_coins,): (u32,)| {
+  ///     panic!("Not run in a dry run");
+  ///   });
+  ///   When!(runner, "^I spend (\\d+) coins$", |_, _: &mut u32, (_spent, _left): (u32, u32)| {});
+  ///   Then!(runner, "^I am (\\w+)$", |_, _: &mut u32, (_age,): (u32,)| {});
+  ///   runner.then(file!(), line!(), Regex::new("^I feel happy$").unwrap(), Box::new(|_, _, _| {
+  ///     panic!("Not run in a dry run either");
+  ///   }));
+  ///
+  ///   let feature = gherkin::parse("coins.feature",
+  ///                                "Feature: Coins\n  Scenario: Rich\n    Given I have 5 \
+  ///                                 coins\n    When I spend 2 coins\n    Then I am rich\n    \
+  ///                                 Then I feel happy\n")
+  ///     .unwrap();
+  ///   let summary = runner.run(&[feature], &mut Pretty::new(Vec::new(), false));
+  ///   assert_eq!(summary.steps.skipped, 2);
+  ///   // One argument is missing, and "rich" is not a number
+  ///   assert_eq!(summary.steps.failed, 2);
+  /// }
+  /// ```
+  pub fn dry_run(mut self, enabled: bool) -> NativeRunner<World> {
+    self.dry_run = enabled;
+    self
+  }
+
+  /// Run scenarios on this many threads at once
+  ///
+  /// Each thread makes its own Worlds, and shares the steps and hooks with
+  /// the others. Scenarios tagged `@serial`, or whose feature, rule or
+  /// examples are, wait for the scenarios before them to finish and run on
+  /// their own. The formatter is told about one scenario at a time, once it
+  /// has finished, in the order of the features. What steps print is not
+  /// captured, as capturing works on the stdout of the whole process.
+  pub fn threads(mut self, threads: usize) -> NativeRunner<World> {
+    self.threads = threads;
+    self
+  }
+
+  /// The steps registered with this runner
+  pub fn cucumber(&self) -> &Cucumber<World> {
+    &self.cuke
+  }
+
+  /// Run every selected scenario of the features, in order
+  pub fn run(&mut self, features: &[Feature], formatter: &mut Formatter) -> Summary
+    where World: 'static
+  {
+    let start = Instant::now();
+    let mut summary = Summary { strictness: self.strictness, ..Summary::default() };
+    let features = self.select(features);
+    formatter.run_started(&features);
+
+    if self.threads > 1 {
+      self.run_parallel(features, formatter, &mut summary);
+    } else {
+      self.run_serial(&features, formatter, &mut summary);
+    }
+    // NOTE: Fail fast runs return here too, so they run these as well
+    if !self.dry_run {
+      summary.after_all_failures = run_after_all_hooks(&self.cuke);
+    }
+
+    summary.duration = start.elapsed();
+    formatter.run_finished(&summary);
+    summary
+  }
+
+  fn run_serial(&self, features: &[Feature], formatter: &mut Formatter, summary: &mut Summary) {
+    let mut scenarios = self.scenario_runner(self.captured_fd);
+
+    for feature in features {
+      formatter.feature_started(feature);
+
+      for scenario in &feature.scenarios {
+        let result = scenarios.run(feature, scenario, formatter);
+        summary.add(&result);
+
+        if self.fail_fast && !summary.passed() {
+          formatter.feature_finished(feature);
+          return;
+        }
+      }
+
+      formatter.feature_finished(feature);
+    }
+  }
+
+  // Runs the scenarios on worker threads, telling the formatter about them in
+  // order as they finish
+  fn run_parallel(&self, features: Vec<Feature>, formatter: &mut Formatter, summary: &mut Summary)
+    where World: 'static
+  {
+    let features = Arc::new(features);
+    let jobs: Vec<(usize, usize)> = features.iter()
+      .enumerate()
+      .flat_map(|(f, feature)| (0..feature.scenarios.len()).map(move |s| (f, s)))
+      .collect();
+    let serial: Vec<bool> = jobs.iter()
+      .map(|&(f, s)| is_serial(&features[f], &features[f].scenarios[s]))
+      .collect();
+
+    let (job_tx, job_rx) = channel::<(usize, usize, usize)>();
+    let job_rx = Arc::new(Mutex::new(job_rx));
+    // The first scenario known to fail a fail fast run, after which no
+    // scenario is started
+    let stop_after = Arc::new(AtomicUsize::new(usize::MAX));
+    let (result_tx, result_rx) = channel();
+    let workers: Vec<JoinHandle<()>> = (0..self.threads)
+      .map(|_| {
+        // NOTE: Capturing works on the stdout of the whole process, which the
+        // threads would be fighting over
+        let mut scenarios = self.scenario_runner(None);
+        let features = features.clone();
+        let job_rx = job_rx.clone();
+        let result_tx = result_tx.clone();
+        let stop_after = stop_after.clone();
+        thread::spawn(move || {
+          loop {
+            let job = job_rx.lock().unwrap().recv();
+            let (index, f, s) = match job {
+              Ok(job) => job,
+              Err(_) => break,
+            };
+            // NOTE: Only scenarios after the failure are dropped, so every one
+            // that is reported still has a result
+            if index > stop_after.load(Ordering::SeqCst) {
+              continue;
+            }
+            let (feature, scenario) = (&features[f], &features[f].scenarios[s]);
+            let mut recording = Recording::new();
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+              scenarios.run(feature, scenario, &mut recording)
+            }));
+            let _ = result_tx.send((index, outcome.map(|result| (recording, result))));
+          }
+        })
+      })
+      .collect();
+    drop(result_tx);
+
+    let mut dispatched = 0;
+    let mut running = 0;
+    let mut serial_running = false;
+    let mut finished = BTreeMap::new();
+    let mut reported = 0;
+    let mut feature_started = None;
+
+    'reporting: while reported < jobs.len() {
+      // Scenarios tagged @serial wait for those before them to finish, and
+      // those after them wait for them
+      while dispatched < jobs.len() && dispatched <= stop_after.load(Ordering::SeqCst) &&
+            running < self.threads && !serial_running &&
+            (!serial[dispatched] || running == 0) {
+        let (f, s) = jobs[dispatched];
+        job_tx.send((dispatched, f, s)).unwrap();
+        serial_running = serial[dispatched];
+        dispatched += 1;
+        running += 1;
+      }
+
+      let (index, outcome) = result_rx.recv().unwrap();
+      running -= 1;
+      if serial[index] {
+        serial_running = false;
+      }
+      match outcome {
+        Ok((recording, result)) => {
+          if self.fail_fast && self.strictness.fails(result.status) &&
+             index < stop_after.load(Ordering::SeqCst) {
+            stop_after.store(index, Ordering::SeqCst);
+          }
+          finished.insert(index, (recording, result))
+        },
+        Err(payload) => panic::resume_unwind(payload),
+      };
+
+      while let Some((recording, result)) = finished.remove(&reported) {
+        let (f, s) = jobs[reported];
+        let feature = &features[f];
+        if feature_started != Some(f) {
+          if let Some(previous) = feature_started {
+            formatter.feature_finished(&features[previous]);
+          }
+          formatter.feature_started(feature);
+          feature_started = Some(f);
+        }
+
+        recording.replay(feature, &feature.scenarios[s], formatter);
+        summary.add(&result);
+        reported += 1;
+
+        if self.fail_fast && !summary.passed() {
+          break 'reporting;
+        }
+      }
+    }
+
+    if let Some(f) = feature_started {
+      formatter.feature_finished(&features[f]);
+    }
+    // NOTE: Scenarios still running when a fail fast run stops are let finish,
+    // but not reported, and those not yet started are dropped
+    drop(job_tx);
+    for worker in workers {
+      let _ = worker.join();
+    }
+  }
+
+  // Whether a scenario is chosen by every filter
+  fn selected(&self, feature: &Feature, scenario: &Scenario) -> bool {
+    let tags_match = self.tags
+      .as_ref()
+      .map_or(true, |expression| Tags::of_scenario(feature, scenario).matches(expression));
+    let name_matches = self.names.is_empty() ||
+                       self.names.iter().any(|regex| regex.is_match(&scenario.name));
+    let lines = self.lines
+      .iter()
+      .filter(|&&(ref path, _)| *path == feature.path)
+      .flat_map(|&(_, ref lines)| lines.iter())
+      .collect::<Vec<_>>();
+    let at_lines = lines.is_empty() ||
+                   lines.into_iter().any(|&line| at_line(feature, scenario, line));
+
+    tags_match && name_matches && at_lines
+  }
+
+  // The features with only their selected scenarios, leaving out those with
+  // none
+  fn select(&self, features: &[Feature]) -> Vec<Feature> {
+    features.iter()
+      .filter_map(|feature| {
+        let scenarios: Vec<Scenario> = feature.scenarios
+          .iter()
+          .filter(|scenario| self.selected(feature, scenario))
+          .cloned()
+          .collect();
+        if scenarios.is_empty() {
+          None
+        } else {
+          Some(Feature { scenarios: scenarios, ..feature.clone() })
+        }
+      })
+      .collect()
+  }
+
+  // Runs scenarios with a Cucumber of its own, to be moved to another thread
+  fn scenario_runner(&self, captured_fd: Option<libc::c_int>) -> ScenarioRunner<World> {
+    let mut cuke = self.cuke.share();
+    cuke.set_dry_run(self.dry_run);
+    ScenarioRunner {
+      cuke: cuke,
+      new_world: self.new_world.clone(),
+      snippet_style: self.snippet_style,
+      captured_fd: captured_fd,
+      retries: self.retries,
+    }
+  }
+}
+
+// Runs scenarios, each with a fresh World, retrying those that fail
+struct ScenarioRunner<World> {
+  cuke: Cucumber<World>,
+  new_world: Arc<Fn() -> World + Send + Sync>,
+  snippet_style: SnippetStyle,
+  captured_fd: Option<libc::c_int>,
+  retries: usize,
+}
+
+impl<World> ScenarioRunner<World> {
+  // Runs a scenario for as many attempts as it takes, returning the result of
+  // the last one
+  fn run(&mut self,
+         feature: &Feature,
+         scenario: &Scenario,
+         formatter: &mut Formatter)
+         -> ScenarioResult {
+    let retries = retries_of(&Tags::of_scenario(feature, scenario)).unwrap_or(self.retries);
+    let mut attempt = 1;
+    let mut result = self.run_attempt(feature, scenario, attempt, retries > 0, formatter);
+    while result.retried {
+      attempt += 1;
+      result = self.run_attempt(feature, scenario, attempt, attempt <= retries, formatter);
+    }
+    result
+  }
+
+  fn run_attempt(&mut self,
+                 feature: &Feature,
+                 scenario: &Scenario,
+                 attempt: usize,
+                 can_retry: bool,
+                 formatter: &mut Formatter)
+                 -> ScenarioResult {
+    let start = Instant::now();
+    let dry_run = self.cuke.dry_run();
+    formatter.scenario_started(feature, scenario);
+
+    self.cuke.set_tags(Tags::of_scenario(feature, scenario));
+    let mut world = (self.new_world)();
+    let mut hooks = if dry_run {
+      Vec::new()
+    } else {
+      self.run_hooks(HookKind::Before, &mut world, feature, scenario, formatter)
+    };
+    let mut results: Vec<StepResult> = Vec::new();
+    // And, But and * continue the kind of step before them, which matters
+    // for snippets
+    let mut keyword = "Given";
+
+    for step in feature.background_steps(scenario).into_iter().chain(scenario.steps.iter()) {
+      keyword = match step.keyword.as_str() {
+        "Given" => "Given",
+        "When" => "When",
+        "Then" => "Then",
+        _ => keyword,
+      };
+
+      // NOTE: Dry runs check every step, as none of them do anything
+      let skip = !dry_run &&
+                 hooks.iter()
+                   .map(|hook| hook.status)
+                   .chain(results.iter().map(|result| result.status))
+                   .any(|status| status != StepStatus::Passed);
+      let matches = self.cuke.find_match(&step.text);
+      formatter.step_matched(feature, scenario, step, &matches);
+      let result = self.run_step(&mut world, keyword, step, matches, skip);
+      formatter.step_finished(feature, scenario, step, &result);
+      results.push(result);
+    }
+
+    if !dry_run {
+      hooks.extend(self.run_hooks(HookKind::After, &mut world, feature, scenario, formatter));
+    }
+    self.cuke.set_tags(Tags::new());
+
+    let before = hooks.iter().filter(|hook| hook.kind == HookKind::Before);
+    let after = hooks.iter().filter(|hook| hook.kind == HookKind::After);
+    // NOTE: Every step of a dry run is checked, so one that did not fit is
+    // reported over those skipped before it
+    let passing = if dry_run { StepStatus::Skipped } else { StepStatus::Passed };
+    let status = before.map(|hook| hook.status)
+      .chain(results.iter().map(|result| result.status))
+      .chain(after.map(|hook| hook.status))
+      .find(|&status| status != passing)
+      .unwrap_or(passing);
+    let result = ScenarioResult {
+      status: status,
+      steps: results,
+      hooks: hooks,
+      duration: start.elapsed(),
+      attempt: attempt,
+      // NOTE: Only failures are retried, as pending, undefined and ambiguous
+      // steps would not change between attempts
+      retried: status == StepStatus::Failed && can_retry && !dry_run,
+    };
+    formatter.scenario_finished(feature, scenario, &result);
+    result
+  }
+
+  fn run_hooks(&self,
+               kind: HookKind,
+               world: &mut World,
+               feature: &Feature,
+               scenario: &Scenario,
+               formatter: &mut Formatter)
+               -> Vec<HookResult> {
+    // NOTE: As in wire runs, every After hook runs, while the Before hooks
+    // after one that did not pass are skipped
+    let mut skip = false;
+    self.cuke
+      .hooks(kind)
+      .into_iter()
+      .map(|(source, hook)| {
+        let start = Instant::now();
+        let (status, response) = if skip {
+          (StepStatus::Skipped, InvokeResponse::Success)
+        } else {
+          let response = catch_to_response(|| hook(&self.cuke, world));
+          (status_of(&response), response)
+        };
+        skip = kind == HookKind::Before && status != StepStatus::Passed;
+        let result = HookResult {
+          kind: kind,
+          source: source.to_owned(),
+          status: status,
+          response: response,
+          duration: start.elapsed(),
+        };
+        formatter.hook_finished(feature, scenario, &result);
+        result
+      })
+      .collect()
+  }
+
+  fn run_step(&self,
+              world: &mut World,
+              keyword: &str,
+              step: &Step,
+              mut matches: Vec<MatchedStep>,
+              skip: bool)
+              -> StepResult {
+    let start = Instant::now();
+
+    let mut result = StepResult {
+      status: StepStatus::Skipped,
+      matched: None,
+      response: None,
+      snippet: None,
+      output: String::new(),
+      duration: Duration::new(0, 0),
+    };
+
+    match matches.len() {
+      0 => {
+        let multiline_arg_class =
+          step.argument.as_ref().map(|arg| arg.multiline_arg_class()).unwrap_or("");
+        result.status = StepStatus::Undefined;
+        result.snippet =
+          Some(snippet::snippet_text(self.snippet_style, keyword, &step.text, multiline_arg_class));
+      },
+      1 => {
+        let matched = matches.pop().unwrap();
+        let id = StepId::from_str(&matched.id).unwrap();
+        // NOTE: Dry runs can only check the arguments of the macro steps
+        let invoke = !self.cuke.dry_run() || self.cuke.step_checks_arguments(id);
+        if !skip && invoke {
+          let capture = self.captured_fd.and_then(|fd| Capture::start(fd).ok());
+          let response = invoke_to_response(self.cuke.step(id).unwrap(),
+                                            &self.cuke,
+                                            world,
+                                            invoke_args(&matched, step));
+          if let Some(capture) = capture {
+            result.output = capture.finish().unwrap_or_else(|_| String::new());
+          }
+          result.status = match status_of(&response) {
+            StepStatus::Passed if self.cuke.dry_run() => StepStatus::Skipped,
+            status => status,
+          };
+          result.response = Some(response);
+        }
+        result.matched = Some(matched);
+      },
+      _ => {
+        let sources: Vec<String> = matches.into_iter().map(|matched| matched.source).collect();
+        result.status = StepStatus::Ambiguous;
+        result.response = Some(InvokeResponse::Fail(FailMessage::new(format!(
+          "Ambiguous match of \"{}\", matching step definitions at {}",
+          step.text,
+          sources.join(", ")))));
+      },
+    }
+
+    result.duration = start.elapsed();
+    result
+  }
+}
+
+// An event of a scenario run on a worker thread
+enum Event {
+  ScenarioStarted,
+  HookFinished(HookResult),
+  /// The index of the step among the background steps and those of the
+  /// scenario, and its matches
+  StepMatched(usize, Vec<MatchedStep>),
+  StepFinished(usize, StepResult),
+  ScenarioFinished(ScenarioResult),
+}
+
+// Keeps the events of a scenario, to tell a formatter about them once every
+// scenario before it has been reported
+struct Recording {
+  events: Vec<Event>,
+  step: usize,
+}
+
+impl Recording {
+  fn new() -> Recording {
+    Recording {
+      events: Vec::new(),
+      step: 0,
+    }
+  }
+
+  fn replay(self, feature: &Feature, scenario: &Scenario, formatter: &mut Formatter) {
+    let mut steps = feature.background_steps(scenario);
+    steps.extend(scenario.steps.iter());
+    for event in self.events {
+      match event {
+        Event::ScenarioStarted => formatter.scenario_started(feature, scenario),
+        Event::HookFinished(result) => formatter.hook_finished(feature, scenario, &result),
+        Event::StepMatched(step, matches) => {
+          formatter.step_matched(feature, scenario, steps[step], &matches)
+        },
+        Event::StepFinished(step, result) => {
+          formatter.step_finished(feature, scenario, steps[step], &result)
+        },
+        Event::ScenarioFinished(result) => formatter.scenario_finished(feature, scenario, &result),
+      }
+    }
+  }
+}
+
+// NOTE: Steps are matched and finished in order, so counting them is enough to
+// tell which one an event is about
+impl Formatter for Recording {
+  fn scenario_started(&mut self, _: &Feature, _: &Scenario) {
+    self.events.push(Event::ScenarioStarted);
+    self.step = 0;
+  }
+
+  fn hook_finished(&mut self, _: &Feature, _: &Scenario, result: &HookResult) {
+    self.events.push(Event::HookFinished(result.clone()));
+  }
+
+  fn step_matched(&mut self, _: &Feature, _: &Scenario, _: &Step, matches: &[MatchedStep]) {
+    self.events.push(Event::StepMatched(self.step, matches.to_vec()));
+  }
+
+  fn step_finished(&mut self, _: &Feature, _: &Scenario, _: &Step, result: &StepResult) {
+    self.events.push(Event::StepFinished(self.step, result.clone()));
+    self.step += 1;
+  }
+
+  fn scenario_finished(&mut self, _: &Feature, _: &Scenario, result: &ScenarioResult) {
+    self.events.push(Event::ScenarioFinished(result.clone()));
+  }
+}
+
+fn status_of(response: &InvokeResponse) -> StepStatus {
+  match response {
+    &InvokeResponse::Success => StepStatus::Passed,
+    &InvokeResponse::Pending(_) => StepStatus::Pending,
+    &InvokeResponse::Fail(_) => StepStatus::Failed,
+  }
+}
+
+// Whether a scenario is tagged to run while no other scenario does
+fn is_serial(feature: &Feature, scenario: &Scenario) -> bool {
+  Tags::of_scenario(feature, scenario).contains("@serial")
+}
+
+// How many times the `@retry(n)` tag of a scenario, or the most specific one
+// if it inherits several, says it may be retried
+fn retries_of(tags: &Tags) -> Option<usize> {
+  tags.names()
+    .iter()
+    .filter_map(|tag| {
+      if tag.starts_with("@retry(") && tag.ends_with(')') {
+        tag["@retry(".len()..tag.len() - 1].parse().ok()
+      } else {
+        None
+      }
+    })
+    .last()
+}
+
+// Whether a line of a feature file belongs to a scenario, as described for
+// `NativeRunner::filter_lines`
+fn at_line(feature: &Feature, scenario: &Scenario, line: usize) -> bool {
+  line == feature.line || line == scenario.line || Some(line) == scenario.outline_line ||
+  scenario.steps.iter().any(|step| step.line == line)
+}
+
+fn invoke_args(matched: &MatchedStep, step: &Step) -> Vec<InvokeArgument> {
+  let mut args: Vec<InvokeArgument> =
+    matched.args.iter().cloned().map(InvokeArgument::from_step_arg).collect();
+
+  match step.argument {
+    Some(StepArgument::DocString(ref content)) => {
+      args.push(InvokeArgument::String(content.clone()))
+    },
+    Some(StepArgument::Table(ref rows)) => args.push(InvokeArgument::Table(rows.clone())),
+    None => {},
+  }
+
+  args
+}
+
+impl<World> CucumberRegistrar<World> for NativeRunner<World> {
+  fn given(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
+    self.cuke.given(file, line, regex, step)
+  }
+
+  fn when(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
+    self.cuke.when(file, line, regex, step)
+  }
+
+  fn then(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
+    self.cuke.then(file, line, regex, step)
+  }
+
+  fn timeout(&mut self, regex: &str, timeout: Duration) {
+    self.cuke.timeout(regex, timeout)
+  }
+
+  fn checks_arguments(&mut self, regex: &str) {
+    self.cuke.checks_arguments(regex)
+  }
+
+  fn before(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.cuke.before(file, line, tags, hook)
+  }
+
+  fn after(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.cuke.after(file, line, tags, hook)
+  }
+
+  fn after_all(&mut self, file: &str, line: u32, hook: RunHook<World>) {
+    self.cuke.after_all(file, line, hook)
+  }
+}
+
+#[cfg(test)]
+pub mod test {
+  use super::*;
+
+  use std::cell::RefCell;
+  use std::fs::File;
+  use std::io::{self, Write};
+  use std::rc::Rc;
+  use std::sync::{Arc, Mutex};
+  use std::sync::atomic::AtomicBool;
+
+  use cucumber_regex;
+  use definitions::registration::{CucumberRegistrar, StepKeyword};
+  use event::request::InvokeArgument;
+  use event::response::Step as MatchedStep;
+  use format::Formatter;
+  use gherkin::{self, Feature, Scenario, Step};
+
+  // Remembers the statuses of the steps it is told about
+  struct Statuses(Vec<(String, StepStatus)>);
+
+  impl Formatter for Statuses {
+    fn step_finished(&mut self,
+                     _: &Feature,
+                     _: &Scenario,
+                     step: &Step,
+                     result: &StepResult) {
+      self.0.push((step.text.clone(), result.status));
+    }
+  }
+
+  /// A step that fails only the first time it runs, with `not this time`
+  pub fn flaky_step<World>() -> SimpleStep<World> {
+    let failed = AtomicBool::new(false);
+    Box::new(move |c, _, _| if !failed.swap(true, Ordering::SeqCst) {
+      c.fail("not this time")
+    })
+  }
+
+  /// A runner with the steps the formatter tests share, all in `steps.rs`:
+  ///
+  /// - `I have <n>` and `it works`, which pass
+  /// - `it breaks`, which fails with `it broke`
+  /// - `it waits`, which is pending with `later`
+  pub fn formatter_runner() -> NativeRunner<u32> {
+    let mut runner = NativeRunner::new(|| 0u32);
+    runner.given("steps.rs", 1, cucumber_regex::build("^I have (\\d+)$"), Box::new(|_, _, _| ()));
+    runner.given("steps.rs", 2, cucumber_regex::build("^it works$"), Box::new(|_, _, _| ()));
+    runner.then("steps.rs",
+                3,
+                cucumber_regex::build("^it breaks$"),
+                Box::new(|c, _, _| c.fail("it broke")));
+    runner.then("steps.rs",
+                4,
+                cucumber_regex::build("^it waits$"),
+                Box::new(|c, _, _| c.pending("later")));
+    runner
+  }
+
+  /// What a formatter of a test writes, to read back once the run is over
+  #[derive(Clone, Default)]
+  pub struct Output(Rc<RefCell<Vec<u8>>>);
+
+  impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+      self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+      Ok(())
+    }
+  }
+
+  /// Runs a feature, as `test.feature`, yielding what the formatter made of
+  /// the run
+  pub fn run_with<World, F, M>(runner: &mut NativeRunner<World>,
+                               feature: &str,
+                               formatter: M)
+                               -> String
+    where World: 'static,
+          F: Formatter,
+          M: FnOnce(Output) -> F
+  {
+    run_features(runner, &[("test.feature", feature)], formatter)
+  }
+
+  /// Runs features, given as their path and source, yielding what the
+  /// formatter made of the run
+  pub fn run_features<World, F, M>(runner: &mut NativeRunner<World>,
+                                   sources: &[(&str, &str)],
+                                   formatter: M)
+                                   -> String
+    where World: 'static,
+          F: Formatter,
+          M: FnOnce(Output) -> F
+  {
+    let features: Vec<Feature> = sources.iter()
+      .map(|&(path, source)| gherkin::parse(path, source).unwrap())
+      .collect();
+    let output = Output::default();
+    runner.run(&features, &mut formatter(output.clone()));
+    let written = output.0.borrow().clone();
+    String::from_utf8(written).unwrap()
+  }
+
+  /// A runner capturing what steps print to a file, rather than to stdout,
+  /// which the test harness writes to
+  #[cfg(unix)]
+  pub fn capturing<World>(mut runner: NativeRunner<World>, file: &File) -> NativeRunner<World> {
+    use std::os::unix::io::AsRawFd;
+
+    runner.captured_fd = Some(file.as_raw_fd());
+    runner
+  }
+
+  /// A step printing `Hello from a step` to a file, as one printing to
+  /// stdout would when capturing from it
+  pub fn chatty_step<World>(file: Arc<File>) -> SimpleStep<World> {
+    Box::new(move |_, _, _| (&*file).write_all(b"Hello from a step\n").unwrap())
+  }
+
+  fn number(args: &[InvokeArgument]) -> u32 {
+    match args[0] {
+      InvokeArgument::String(ref value) => value.parse().unwrap(),
+      _ => panic!("expected a number"),
+    }
+  }
+
+  fn runner() -> NativeRunner<u32> {
+    let mut runner = NativeRunner::new(|| 0u32);
+    runner.given("file",
+                 1,
+                 cucumber_regex::build("^I add (\\d+)$"),
+                 Box::new(|_, world: &mut u32, args| *world += number(&args)));
+    runner.then("file",
+                2,
+                cucumber_regex::build("^I have (\\d+)$"),
+                Box::new(|_, world: &mut u32, args| assert_eq!(*world, number(&args))));
+    runner.when("file",
+                3,
+                cucumber_regex::build("^I (add|remove) 5$"),
+                Box::new(|_, _, _| ()));
+    runner.when("file",
+                4,
+                cucumber_regex::build("^I wait$"),
+                Box::new(|c, _, _| c.pending("not yet")));
+    runner
+  }
+
+  fn feature() -> Feature {
+    gherkin::parse("test.feature",
+                   "Feature: Counting\n  Background:\n    Given I add 1\n  Scenario: Passing\n    \
+                    And I add 2\n    Then I have 3\n  Scenario: Failing\n    Then I have 2\n    And \
+                    I add 1\n  Scenario: Pending\n    When I wait\n    Then I add 1\n  Scenario: \
+                    Undefined\n    When I fly\n  Scenario: Ambiguous\n    Then I add 5\n")
+      .unwrap()
+  }
+
+  #[test]
+  fn it_runs_features_with_a_fresh_world_per_scenario() {
+    let mut statuses = Statuses(Vec::new());
+    let summary = runner().run(&[feature()], &mut statuses);
+
+    assert_eq!(statuses.0,
+               vec![("I add 1".to_owned(), StepStatus::Passed),
+                    ("I add 2".to_owned(), StepStatus::Passed),
+                    ("I have 3".to_owned(), StepStatus::Passed),
+                    ("I add 1".to_owned(), StepStatus::Passed),
+                    ("I have 2".to_owned(), StepStatus::Failed),
+                    ("I add 1".to_owned(), StepStatus::Skipped),
+                    ("I add 1".to_owned(), StepStatus::Passed),
+                    ("I wait".to_owned(), StepStatus::Pending),
+                    ("I add 1".to_owned(), StepStatus::Skipped),
+                    ("I add 1".to_owned(), StepStatus::Passed),
+                    ("I fly".to_owned(), StepStatus::Undefined),
+                    ("I add 1".to_owned(), StepStatus::Passed),
+                    ("I add 5".to_owned(), StepStatus::Ambiguous)]);
+    assert_eq!(summary.scenarios.passed, 1);
+    assert_eq!(summary.scenarios.failed, 1);
+    assert_eq!(summary.scenarios.pending, 1);
+    assert_eq!(summary.scenarios.undefined, 1);
+    assert_eq!(summary.scenarios.ambiguous, 1);
+    assert_eq!(summary.steps.total(), 13);
+    assert!(!summary.passed());
+  }
+
+  #[test]
+  fn it_reports_failures_and_snippets() {
+    let mut runner = runner();
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Failing\n  Scenario: Failing\n    Given I add 1\n    \
+                                  Then I have 2\n  Scenario: Undefined\n    Given I add 1\n    And \
+                                  I fly\n      | high |\n")
+      .unwrap();
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+
+    struct Results(Arc<Mutex<Vec<StepResult>>>);
+    impl Formatter for Results {
+      fn step_finished(&mut self, _: &Feature, _: &Scenario, _: &Step, result: &StepResult) {
+        self.0.lock().unwrap().push(result.clone());
+      }
+    }
+
+    let summary = runner.run(&[feature], &mut Results(recorded.clone()));
+    let results = recorded.lock().unwrap();
+
+    assert_eq!(results[1].status, StepStatus::Failed);
+    assert!(results[1].failure().unwrap().message().contains("assertion"));
+    assert_eq!(results[1].matched.as_ref().unwrap().source, "file:2");
+    assert_eq!(results[3].status, StepStatus::Undefined);
+    assert!(results[3].snippet.as_ref().unwrap().contains("Given!(cuke, \"^I fly$\""));
+    assert!(results[3].snippet.as_ref().unwrap().contains("(table,): (Vec<Vec<String>>,)"));
+    assert_eq!(summary.scenarios.failed, 1);
+    assert_eq!(summary.scenarios.undefined, 1);
+  }
+
+  // Remembers the events it is told about, in order
+  struct Events(Vec<String>);
+
+  impl Formatter for Events {
+    fn hook_finished(&mut self, _: &Feature, _: &Scenario, result: &HookResult) {
+      self.0.push(format!("{} {}", result.kind.as_str(), result.status.as_str()));
+    }
+
+    fn step_matched(&mut self, _: &Feature, _: &Scenario, step: &Step, matches: &[MatchedStep]) {
+      self.0.push(format!("{} matched {}", step.text, matches.len()));
+    }
+
+    fn step_finished(&mut self, _: &Feature, _: &Scenario, step: &Step, result: &StepResult) {
+      self.0.push(format!("{} {}", step.text, result.status.as_str()));
+    }
+  }
+
+  #[test]
+  fn it_runs_hooks_around_scenarios() {
+    let mut runner = runner();
+    runner.before("hooks", 1, None, Box::new(|_, world: &mut u32| *world = 10));
+    runner.after("hooks", 2, None, Box::new(|_, world: &mut u32| assert_eq!(*world, 11)));
+    runner.after("hooks", 3, None, Box::new(|_, _| ()));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Hooks\n  Scenario: Counting\n    Given I add 1\n    \
+                                  Then I have 11\n  Scenario: Miscounting\n    Given I add 2\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&[feature], &mut events);
+
+    assert_eq!(events.0,
+               vec!["Before passed",
+                    "I add 1 matched 1",
+                    "I add 1 passed",
+                    "I have 11 matched 1",
+                    "I have 11 passed",
+                    "After passed",
+                    "After passed",
+                    "Before passed",
+                    "I add 2 matched 1",
+                    "I add 2 passed",
+                    "After passed",
+                    "After failed"]);
+    assert_eq!(summary.scenarios.passed, 1);
+    assert_eq!(summary.scenarios.failed, 1);
+    assert_eq!(summary.steps.passed, 3);
+  }
+
+  #[test]
+  fn it_skips_steps_after_a_failed_before_hook() {
+    let mut runner = runner();
+    runner.before("hooks", 1, None, Box::new(|c, _| c.fail("no database")));
+    runner.after("hooks", 2, None, Box::new(|_, _| ()));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Hooks\n  Scenario: Counting\n    Given I add 1\n    \
+                                  Given I add 5\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&[feature], &mut events);
+
+    assert_eq!(events.0,
+               vec!["Before failed",
+                    "I add 1 matched 1",
+                    "I add 1 skipped",
+                    "I add 5 matched 2",
+                    "I add 5 ambiguous",
+                    "After passed"]);
+    assert_eq!(summary.scenarios.failed, 1);
+    assert_eq!(summary.scenarios.ambiguous, 0);
+  }
+
+  #[test]
+  fn it_skips_before_hooks_after_a_failed_one() {
+    let mut runner = runner();
+    runner.before("hooks", 1, None, Box::new(|c, _| c.fail("no database")));
+    runner.before("hooks", 2, None, Box::new(|_, world: &mut u32| *world = 10));
+    runner.after("hooks", 3, None, Box::new(|_, world: &mut u32| assert_eq!(*world, 0)));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Hooks\n  Scenario: Counting\n    Given I add 1\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&[feature], &mut events);
+
+    assert_eq!(events.0,
+               vec!["Before failed",
+                    "Before skipped",
+                    "I add 1 matched 1",
+                    "I add 1 skipped",
+                    "After passed"]);
+    assert_eq!(summary.scenarios.failed, 1);
+  }
+
+  #[test]
+  fn it_runs_scenarios_matching_tags() {
+    let mut runner = runner().filter_tags(TagExpression::parse("@fast and not @wip").unwrap());
+    let features = vec![gherkin::parse("fast.feature",
+                                       "@fast\nFeature: Fast\n  Scenario: Done\n    Given I add \
+                                        1\n  @wip\n  Scenario: Unfinished\n    Given I add 2\n")
+                          .unwrap(),
+                        gherkin::parse("slow.feature",
+                                       "Feature: Slow\n  Scenario: Waiting\n    Given I add 3\n")
+                          .unwrap()];
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&features, &mut events);
+
+    assert_eq!(events.0, vec!["I add 1 matched 1", "I add 1 passed"]);
+    assert_eq!(summary.scenarios.passed, 1);
+  }
+
+  #[test]
+  fn it_runs_scenarios_at_lines_and_with_names() {
+    let source = "Feature: Selection\n  Scenario: First\n    Given I add 1\n  Scenario: \
+                  Second\n    Given I add 2\n  Scenario Outline: Third\n    Given I add <n>\n    \
+                  Examples:\n      | n |\n      | 3 |\n      | 4 |\n";
+    let features = vec![gherkin::parse("a.feature", source).unwrap(),
+                        gherkin::parse("b.feature", source).unwrap()];
+    let added = |runner: NativeRunner<u32>| {
+      let mut events = Events(Vec::new());
+      let mut runner = runner;
+      runner.run(&features, &mut events);
+      events.0
+        .into_iter()
+        .filter(|event| event.ends_with("passed"))
+        .map(|event| event.replace("I add ", "").replace(" passed", ""))
+        .collect::<Vec<_>>()
+    };
+
+    // A scenario's keyword and steps, and a single row of examples
+    assert_eq!(added(runner().filter_lines("a.feature", vec![5, 11])),
+               vec!["2", "4", "1", "2", "3", "4"]);
+    // An outline selects all of its rows, and the feature all scenarios
+    assert_eq!(added(runner()
+                 .filter_lines("a.feature", vec![6])
+                 .filter_lines("b.feature", vec![1])),
+               vec!["3", "4", "1", "2", "3", "4"]);
+    assert_eq!(added(runner().filter_name(Regex::new("^(First|Third)").unwrap())),
+               vec!["1", "3", "4", "1", "3", "4"]);
+    assert_eq!(added(runner()
+                 .filter_name(Regex::new("ir").unwrap())
+                 .filter_lines("b.feature", vec![2])),
+               vec!["1", "3", "4", "1"]);
+  }
+
+  #[test]
+  fn it_retries_failed_scenarios() {
+    let mut runner = runner().retry(1);
+    runner.then("file", 5, cucumber_regex::build("^it is flaky$"), flaky_step());
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Retries\n  Scenario: Lucky\n    Then it is flaky\n  \
+                                  @retry(2)\n  Scenario: Broken\n    Then I have 1\n  \
+                                  @retry(0)\n  Scenario: Once\n    Then I have 1\n")
+      .unwrap();
+
+    struct Attempts(Vec<(usize, StepStatus, bool)>);
+    impl Formatter for Attempts {
+      fn scenario_finished(&mut self, _: &Feature, _: &Scenario, result: &ScenarioResult) {
+        self.0.push((result.attempt, result.status, result.retried));
+      }
+    }
+    let mut attempts = Attempts(Vec::new());
+    let summary = runner.run(&[feature], &mut attempts);
+
+    assert_eq!(attempts.0,
+               vec![(1, StepStatus::Failed, true),
+                    (2, StepStatus::Passed, false),
+                    (1, StepStatus::Failed, true),
+                    (2, StepStatus::Failed, true),
+                    (3, StepStatus::Failed, false),
+                    (1, StepStatus::Failed, false)]);
+    assert_eq!(summary.scenarios.passed, 1);
+    assert_eq!(summary.scenarios.failed, 2);
+    assert_eq!(summary.flaky, 1);
+    assert_eq!(summary.steps.total(), 3);
+  }
+
+  #[test]
+  fn it_fails_strict_runs_on_pending_and_undefined_steps() {
+    let pending = gherkin::parse("test.feature",
+                                 "Feature: Pending\n  Scenario: Waiting\n    When I wait\n")
+      .unwrap();
+    let undefined = gherkin::parse("test.feature",
+                                   "Feature: Undefined\n  Scenario: Missing\n    When I \
+                                    vanish\n")
+      .unwrap();
+    let run = |strictness, feature: &Feature| {
+      runner().strict(strictness).run(&[feature.clone()], &mut Events(Vec::new())).passed()
+    };
+
+    assert!(run(Strictness::default(), &pending));
+    assert!(run(Strictness::default(), &undefined));
+    assert!(!run(Strictness::strict(), &pending));
+    assert!(!run(Strictness::strict(), &undefined));
+    let pending_only = Strictness { pending: true, undefined: false };
+    assert!(!run(pending_only, &pending));
+    assert!(run(pending_only, &undefined));
+  }
+
+  #[test]
+  fn it_only_matches_steps_in_dry_runs() {
+    let mut runner = runner().dry_run(true);
+    runner.before("hooks", 1, None, Box::new(|_, _| panic!("hooks are not run")));
+    runner.then("file",
+                5,
+                cucumber_regex::build("^it is dry$"),
+                Box::new(|_, _, _| panic!("steps without argument checks are not invoked")));
+    runner.register_step(StepKeyword::Then,
+                         "file",
+                         6,
+                         cucumber_regex::build("^it is checked$"),
+                         None,
+                         Box::new(|c, _, _| assert!(c.dry_run())));
+    runner.register_step(StepKeyword::Then,
+                         "file",
+                         7,
+                         cucumber_regex::build("^it is broken$"),
+                         None,
+                         Box::new(|_, _, _| panic!("The arguments do not fit")));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Dry\n  Scenario: Dry\n    Then it is dry\n    \
+                                  Then it is checked\n    Then it is broken\n    Then it is \
+                                  wet\n    Then it is dry\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&[feature], &mut events);
+    assert_eq!(events.0,
+               vec!["it is dry matched 1",
+                    "it is dry skipped",
+                    "it is checked matched 1",
+                    "it is checked skipped",
+                    "it is broken matched 1",
+                    "it is broken failed",
+                    "it is wet matched 0",
+                    "it is wet undefined",
+                    "it is dry matched 1",
+                    "it is dry skipped"]);
+    assert_eq!(summary.scenarios.failed, 1);
+    assert!(!runner.cucumber().dry_run());
+  }
+
+  #[test]
+  fn it_runs_scenarios_on_threads() {
+    use std::thread;
+    use std::time::Duration;
+
+    // How many scenarios are running, and the most that ever were at once
+    let running = Arc::new(Mutex::new((0, 0)));
+    let build = |threads| {
+      let mut runner = runner().threads(threads);
+      let counts = running.clone();
+      runner.before("hooks",
+                    1,
+                    None,
+                    Box::new(move |_, _| {
+                      let mut counts = counts.lock().unwrap();
+                      counts.0 += 1;
+                      counts.1 = counts.1.max(counts.0);
+                    }));
+      let counts = running.clone();
+      runner.after("hooks", 2, None, Box::new(move |_, _| counts.lock().unwrap().0 -= 1));
+      runner.then("file",
+                  5,
+                  cucumber_regex::build("^I pause$"),
+                  Box::new(|_, _, _| thread::sleep(Duration::from_millis(50))));
+      let counts = running.clone();
+      runner.then("file",
+                  6,
+                  cucumber_regex::build("^I am alone$"),
+                  Box::new(move |_, _, _| assert_eq!(counts.lock().unwrap().0, 1)));
+      runner
+    };
+    let features = vec![gherkin::parse("a.feature",
+                                       "Feature: A\n  Scenario: One\n    Given I add 1\n    \
+                                        Then I pause\n    Then I have 1\n  Scenario: Two\n    \
+                                        Given I add 2\n    Then I pause\n    Then I have 3\n  \
+                                        @serial\n  Scenario: Alone\n    Then I pause\n    \
+                                        Then I am alone\n")
+                          .unwrap(),
+                        gherkin::parse("b.feature",
+                                       "Feature: B\n  Scenario: Three\n    Then I pause\n    \
+                                        Then I wait\n  Scenario: Four\n    Given I add 4\n    \
+                                        Then I pause\n    Then I have 4\n")
+                          .unwrap()];
+
+    let mut serial = Events(Vec::new());
+    let serial_summary = build(1).run(&features, &mut serial);
+    assert_eq!(running.lock().unwrap().1, 1);
+
+    let mut parallel = Events(Vec::new());
+    let parallel_summary = build(3).run(&features, &mut parallel);
+    assert_eq!(parallel.0, serial.0);
+    assert_eq!(parallel_summary.scenarios, serial_summary.scenarios);
+    assert_eq!(parallel_summary.scenarios.failed, 1);
+    assert_eq!(parallel_summary.scenarios.pending, 1);
+    assert!(running.lock().unwrap().1 > 1);
+  }
+
+  #[test]
+  fn it_stops_after_the_first_failure() {
+    let features = vec![gherkin::parse("a.feature",
+                                       "Feature: A\n  Scenario: Working\n    Given I add \
+                                        1\n  Scenario: Broken\n    Then I have 2\n  \
+                                        Scenario: Skipped\n    Given I add 1\n")
+                          .unwrap(),
+                        gherkin::parse("b.feature",
+                                       "Feature: B\n  Scenario: Skipped\n    Given I add 1\n")
+                          .unwrap()];
+
+    struct Finished(Vec<String>);
+    impl Formatter for Finished {
+      fn scenario_finished(&mut self, _: &Feature, scenario: &Scenario, _: &ScenarioResult) {
+        self.0.push(scenario.name.clone());
+      }
+
+      fn feature_finished(&mut self, feature: &Feature) {
+        self.0.push(feature.name.clone());
+      }
+
+      fn run_finished(&mut self, _: &Summary) {
+        self.0.push("run".to_owned());
+      }
+    }
+    let mut finished = Finished(Vec::new());
+    let summary = runner().fail_fast(true).run(&features, &mut finished);
+
+    assert_eq!(finished.0, vec!["Working", "Broken", "A", "run"]);
+    assert_eq!(summary.scenarios.total(), 2);
+    assert!(!summary.passed());
+
+    let mut finished = Finished(Vec::new());
+    let summary = runner().fail_fast(true).threads(2).run(&features, &mut finished);
+    assert_eq!(finished.0, vec!["Working", "Broken", "A", "run"]);
+    assert_eq!(summary.scenarios.total(), 2);
+
+    let summary = runner().run(&features, &mut Finished(Vec::new()));
+    assert_eq!(summary.scenarios.total(), 4);
+  }
+
+  #[test]
+  fn it_runs_rule_backgrounds_after_the_feature_background() {
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Rules\n  Background:\n    Given I add 1\n  Rule: \
+                                  Doubled\n    Background:\n      Given I add 2\n    Example: \
+                                  Sum\n      Then I have 3\n  Rule: Plain\n    Example: \
+                                  One\n      Then I have 1\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner().run(&[feature], &mut events);
+    assert_eq!(summary.scenarios.passed, 2);
+    assert_eq!(summary.steps.total(), 5);
+  }
+
+  #[test]
+  fn it_runs_after_all_hooks_once_the_run_is_over() {
+    let runs = Arc::new(AtomicUsize::new(0));
+    let build = |fail_fast: bool, dry_run: bool| {
+      let mut runner = runner().fail_fast(fail_fast).dry_run(dry_run);
+      let counter = runs.clone();
+      runner.after_all("file",
+                       5,
+                       Box::new(move |_| {
+                         counter.fetch_add(1, Ordering::SeqCst);
+                       }));
+      runner.after_all("file", 6, Box::new(|_| panic!("not cleaned up")));
+      runner
+    };
+
+    let summary = build(false, false).run(&[feature()], &mut Events(Vec::new()));
+    assert_eq!(runs.load(Ordering::SeqCst), 1);
+    assert_eq!(summary.after_all_failures,
+               vec!["AfterAll hook at file:6 failed: not cleaned up".to_owned()]);
+
+    let summary = build(true, false).run(&[feature()], &mut Events(Vec::new()));
+    assert_eq!(summary.scenarios.total(), 2);
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+
+    let summary = build(false, true).run(&[feature()], &mut Events(Vec::new()));
+    assert_eq!(runs.load(Ordering::SeqCst), 2);
+    assert!(summary.after_all_failures.is_empty());
+  }
+
+  #[test]
+  fn it_fails_runs_whose_after_all_hooks_fail() {
+    let mut runner = runner();
+    runner.after_all("file", 5, Box::new(|_| panic!("not cleaned up")));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Passing\n  Scenario: Passing\n    Given I add 1\n")
+      .unwrap();
+
+    let summary = runner.run(&[feature], &mut Events(Vec::new()));
+    assert_eq!(summary.scenarios.passed, 1);
+    assert!(!summary.passed());
+  }
+
+  #[test]
+  fn it_starts_no_scenario_after_a_failure_on_threads() {
+    let started = Arc::new(AtomicUsize::new(0));
+    let counter = started.clone();
+    let mut runner = runner().fail_fast(true).threads(2);
+    runner.given("file",
+                 5,
+                 cucumber_regex::build("^I am slow$"),
+                 Box::new(|_, _, _| thread::sleep(Duration::from_millis(200))));
+    runner.given("file",
+                 6,
+                 cucumber_regex::build("^I count$"),
+                 Box::new(move |_, _, _| {
+                   counter.fetch_add(1, Ordering::SeqCst);
+                 }));
+    // The failure is known while the slow scenario before it is running
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Stop\n  Scenario: Slow\n    Given I am slow\n  \
+                                  Scenario: Broken\n    Then I have 2\n  Scenario: A\n    \
+                                  Given I count\n  Scenario: B\n    Given I count\n  \
+                                  Scenario: C\n    Given I count\n")
+      .unwrap();
+
+    let summary = runner.run(&[feature], &mut Events(Vec::new()));
+    assert_eq!(summary.scenarios.total(), 2);
+    assert_eq!(started.load(Ordering::SeqCst), 0);
+  }
+
+  #[test]
+  fn it_gives_steps_the_scenario_tags() {
+    let mut runner = runner();
+    runner.then("file",
+                5,
+                cucumber_regex::build("^I am tagged (@\\w+)$"),
+                Box::new(|c, _, args| {
+                  let tag = match args[0] {
+                    InvokeArgument::String(ref tag) => tag.clone(),
+                    _ => panic!("Expected a tag"),
+                  };
+                  assert!(c.tags().contains(&tag))
+                }));
+    let feature = gherkin::parse("test.feature",
+                                 "@billing\nFeature: Tags\n  @db\n  Scenario: Tagged\n    Then I \
+                                  am tagged @db\n    Then I am tagged @billing\n  Scenario: \
+                                  Untagged\n    Then I am tagged @db\n")
+      .unwrap();
+
+    let summary = runner.run(&[feature], &mut Events(Vec::new()));
+
+    assert_eq!(summary.scenarios.passed, 1);
+    assert_eq!(summary.scenarios.failed, 1);
+    assert!(runner.cucumber().tags().is_empty());
+  }
+
+  #[test]
+  fn it_runs_hooks_for_matching_tags() {
+    let mut runner = runner();
+    runner.before("hooks",
+                  1,
+                  Some(TagExpression::parse("@db").unwrap()),
+                  Box::new(|_, world: &mut u32| *world = 10));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Hooks\n  @db\n  Scenario: Database\n    Given I add \
+                                  1\n    Then I have 11\n  Scenario: Plain\n    Given I add 1\n    \
+                                  Then I have 1\n")
+      .unwrap();
+
+    let mut events = Events(Vec::new());
+    let summary = runner.run(&[feature], &mut events);
+
+    assert_eq!(events.0.iter().filter(|event| *event == "Before passed").count(), 1);
+    assert_eq!(summary.scenarios.passed, 2);
+  }
+
+  #[cfg(unix)]
+  #[test]
+  fn it_captures_step_output() {
+    use tempdir::TempDir;
+
+    let dir = TempDir::new("cucumber-native").unwrap();
+    let file = Arc::new(File::create(dir.path().join("out.txt")).unwrap());
+    let mut runner = capturing(NativeRunner::new(|| 0u32), &file);
+    runner.given("file", 1, cucumber_regex::build("^a chatty step$"), chatty_step(file.clone()));
+    let feature = gherkin::parse("test.feature",
+                                 "Feature: Output\n  Scenario: Chatty\n    Given a chatty step\n")
+      .unwrap();
+
+    struct Outputs(Vec<String>);
+    impl Formatter for Outputs {
+      fn step_finished(&mut self, _: &Feature, _: &Scenario, _: &Step, result: &StepResult) {
+        self.0.push(result.output.clone());
+      }
+    }
+
+    let mut outputs = Outputs(Vec::new());
+    runner.run(&[feature], &mut outputs);
+    assert!(outputs.0[0].contains("Hello from a step\n"));
+  }
+}
@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+
+use serde_json::{self, Value};
+
+use runner::CommandRunner;
+use event::request::Request;
+use event::response::Response;
+
+/// A [CommandRunner](../runner/trait.CommandRunner.html) that writes every
+/// exchange it handles to a file
+///
+/// Each [Request](../event/request/enum.Request.html) and the
+/// [Response](../event/response/enum.Response.html) produced for it by the
+/// wrapped runner are written as a single line of JSON, `[request, response]`,
+/// using the same representation as the Cucumber Wire Protocol. The resulting
+/// file can be fed back to a runner with [replay](./fn.replay.html).
+///
+/// # Example
+///
+/// ```no_run
+/// use cucumber::{RecordingRunner, Server, WorldRunner};
+///
+/// fn main() {
+///   let world: u32 = 0;
+///   let runner = RecordingRunner::create(WorldRunner::new(world), "wire.ndjson").unwrap();
+///   let server = Server::new(runner);
+///   let (handle, stop_tx) = server.start(None);
+///
+///   /*
+///    * Execute remote gherkin parser
+///    */
+///
+///   stop_tx.send(()).unwrap();
+///   handle.join().unwrap();
+/// }
+/// ```
+pub struct RecordingRunner<R: CommandRunner> {
+  runner: R,
+  out: File,
+}
+
+impl<R: CommandRunner> RecordingRunner<R> {
+  /// Wrap a runner, recording its traffic to a newly created file at `path`
+  pub fn create<P: AsRef<Path>>(runner: R, path: P) -> io::Result<RecordingRunner<R>> {
+    File::create(path).map(|out| {
+      RecordingRunner {
+        runner: runner,
+        out: out,
+      }
+    })
+  }
+
+  /// Unwrap the recording layer, yielding the original runner
+  pub fn into_inner(self) -> R {
+    self.runner
+  }
+}
+
+impl<R: CommandRunner> CommandRunner for RecordingRunner<R> {
+  fn execute_cmd(&mut self, req: Request) -> Response {
+    let line = serde_json::to_string(&req).unwrap();
+    let response = self.runner.execute_cmd(req);
+    let line = format!("[{},{}]\n", line, serde_json::to_string(&response).unwrap());
+
+    // NOTE: A failed recording should not break the run being recorded
+    let _ = self.out.write_all(line.as_bytes());
+
+    response
+  }
+}
+
+/// A recorded exchange for which a runner produced a different response
+#[derive(PartialEq, Clone, Debug)]
+pub struct ReplayMismatch {
+  /// The line of the recording the exchange was read from, starting at 1
+  pub line: usize,
+  pub request: Request,
+  pub expected: Value,
+  pub actual: Value,
+}
+
+/// Reasons a recording could not be replayed
+#[derive(Debug)]
+pub enum ReplayError {
+  Io(io::Error),
+  Malformed {
+    line: usize,
+    message: String,
+  },
+}
+
+impl From<io::Error> for ReplayError {
+  fn from(err: io::Error) -> ReplayError {
+    ReplayError::Io(err)
+  }
+}
+
+/// Feed a session written by a [RecordingRunner](./struct.RecordingRunner.html)
+/// to a runner, comparing its responses against the recorded ones
+///
+/// Every recorded request is executed in order, whether or not earlier
+/// responses matched. Returns the exchanges whose responses differed, so an
+/// empty result means the runner reproduced the session exactly.
+pub fn replay<R: CommandRunner, P: AsRef<Path>>(runner: &mut R,
+                                                path: P)
+                                                -> Result<Vec<ReplayMismatch>, ReplayError> {
+  let reader = BufReader::new(try!(File::open(path)));
+  let mut mismatches = Vec::new();
+
+  for (idx, line) in reader.lines().enumerate() {
+    let line = try!(line);
+    let line_number = idx + 1;
+
+    if line.trim().is_empty() {
+      continue;
+    }
+
+    let (request, expected) = try!(parse_exchange(&line).map_err(|message| {
+      ReplayError::Malformed {
+        line: line_number,
+        message: message,
+      }
+    }));
+
+    let actual = serde_json::to_value(&runner.execute_cmd(request.clone()));
+
+    if actual != expected {
+      mismatches.push(ReplayMismatch {
+        line: line_number,
+        request: request,
+        expected: expected,
+        actual: actual,
+      });
+    }
+  }
+
+  Ok(mismatches)
+}
+
+fn parse_exchange(line: &str) -> Result<(Request, Value), String> {
+  let mut exchange = match try!(serde_json::from_str::<Value>(line).map_err(|e| e.to_string())) {
+    Value::Array(exchange) => exchange,
+    _ => return Err("Expected an exchange of the form [request, response]".to_owned()),
+  };
+
+  if exchange.len() != 2 {
+    return Err(format!("Expected an exchange of two values, but found [{}]", exchange.len()));
+  }
+
+  let response = exchange.pop().unwrap();
+  let request = try!(serde_json::from_value::<Request>(exchange.pop().unwrap())
+    .map_err(|e| e.to_string()));
+
+  Ok((request, response))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use std::fs::File;
+  use std::io::Read;
+  use tempdir::TempDir;
+
+  use runner::CommandRunner;
+  use event::request::{BeginScenarioRequest, Request, StepMatchesRequest};
+  use event::response::{Response, StepMatchesResponse};
+
+  fn respond(req: Request) -> Response {
+    match req {
+      Request::StepMatches(_) => Response::StepMatches(StepMatchesResponse::NoMatch),
+      _ => Response::BeginScenario,
+    }
+  }
+
+  fn record_session(path: &::std::path::Path) {
+    let mut runner = RecordingRunner::create(respond, path).unwrap();
+    runner.execute_cmd(Request::BeginScenario(BeginScenarioRequest { tags: Vec::new() }));
+    runner.execute_cmd(Request::StepMatches(StepMatchesRequest {
+      name_to_match: "a step".to_owned(),
+    }));
+  }
+
+  #[test]
+  fn it_records_exchanges_as_lines() {
+    let dir = TempDir::new("cucumber_recording").unwrap();
+    let path = dir.path().join("session.ndjson");
+    record_session(&path);
+
+    let mut contents = String::new();
+    File::open(&path).unwrap().read_to_string(&mut contents).unwrap();
+    assert_eq!(contents,
+               "[[\"begin_scenario\",{\"tags\":[]}],[\"success\"]]\n[[\"step_matches\",\
+                {\"name_to_match\":\"a step\"}],[\"success\",[]]]\n");
+  }
+
+  #[test]
+  fn it_replays_matching_sessions() {
+    let dir = TempDir::new("cucumber_recording").unwrap();
+    let path = dir.path().join("session.ndjson");
+    record_session(&path);
+
+    let mut runner = respond;
+    assert_eq!(replay(&mut runner, &path).unwrap(), Vec::new());
+  }
+
+  #[test]
+  fn it_reports_replay_mismatches() {
+    let dir = TempDir::new("cucumber_recording").unwrap();
+    let path = dir.path().join("session.ndjson");
+    record_session(&path);
+
+    let mut runner = |_: Request| Response::SnippetText("Snippet".to_owned());
+    let mismatches = replay(&mut runner, &path).unwrap();
+    assert_eq!(mismatches.len(), 2);
+    assert_eq!(mismatches[0].line, 1);
+    assert_eq!(mismatches[1].line, 2);
+  }
+}
@@ -1,9 +1,17 @@
 use regex::Regex;
-use state::Cucumber;
+use state::{Cucumber, StepId};
 use event::request::{InvokeArgument, Request};
-use event::response::{InvokeResponse, Response, StepMatchesResponse};
-use definitions::registration::{CucumberRegistrar, SimpleStep};
+use event::response::{FailMessage, InvokeResponse, Response, StepMatchesResponse};
+use definitions::registration::{CucumberRegistrar, Hook, HookKind, RunHook, SimpleStep};
+use definitions::snippet::{self, SnippetStyle};
+use tags::{TagExpression, Tags};
+use usage::StepUsage;
+use std::cell::Cell;
+use std::fmt;
 use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{Receiver, channel};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use std::str::FromStr;
 
@@ -23,10 +31,21 @@ use std::str::FromStr;
 /// passed to a Server to
 /// maintain.
 ///
+/// Steps with a timeout, either from
+/// [step_timeout](#method.step_timeout) or registered with the step itself,
+/// are executed on a worker thread. If such a step outlives its timeout it is
+/// failed, and the runner fails every further request that needs the World
+/// until the step finally returns it. Steps are still matched and snippets
+/// still written in the meantime.
+///
 #[allow(dead_code)]
 pub struct WorldRunner<World> {
   cuke: Cucumber<World>,
-  world: World,
+  world: Option<World>,
+  step_timeout: Option<Duration>,
+  timed_out: Option<Receiver<(World, InvokeResponse)>>,
+  snippet_style: SnippetStyle,
+  usage: StepUsage,
 }
 
 impl<World> WorldRunner<World> {
@@ -34,11 +53,192 @@ impl<World> WorldRunner<World> {
   pub fn new(world: World) -> WorldRunner<World> {
     WorldRunner {
       cuke: Cucumber::new(),
-      world: world,
+      world: Some(world),
+      step_timeout: None,
+      timed_out: None,
+      snippet_style: SnippetStyle::Regex,
+      usage: StepUsage::new(),
+    }
+  }
+
+  /// Limit how long any step may run before it is failed
+  ///
+  /// Timeouts registered with an individual step take precedence over this
+  /// one.
+  #[allow(dead_code)]
+  pub fn step_timeout(mut self, timeout: Duration) -> WorldRunner<World> {
+    self.step_timeout = Some(timeout);
+    self
+  }
+
+  /// Choose how snippets for undefined steps describe the step
+  #[allow(dead_code)]
+  pub fn snippet_style(mut self, style: SnippetStyle) -> WorldRunner<World> {
+    self.snippet_style = style;
+    self
+  }
+
+  /// The steps registered with this runner
+  pub fn cucumber(&self) -> &Cucumber<World> {
+    &self.cuke
+  }
+
+  /// A handle on the usage of this runner's steps, which keeps being updated
+  /// as the runner handles requests
+  pub fn usage(&self) -> StepUsage {
+    self.usage.clone()
+  }
+}
+
+/// Failures of the runner itself, as opposed to failures of the steps it runs
+///
+/// These are reported to the client as failures whose exception is
+/// `cucumber::RunnerError`, so that they can be told apart from a failing
+/// step.
+#[derive(Eq, PartialEq, Clone, Debug)]
+pub enum RunnerError {
+  /// The id of an invoke request was not a valid step id
+  InvalidStepId(String),
+  /// No step is registered with the id of an invoke request
+  UnknownStepId(StepId),
+  /// A step ran for longer than its timeout
+  StepTimedOut {
+    id: StepId,
+    timeout: Duration,
+  },
+  /// A step which timed out earlier has still not returned the World
+  Busy,
+}
+
+impl fmt::Display for RunnerError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      &RunnerError::InvalidStepId(ref id) => write!(f, "Step id [{}] is not a valid id", id),
+      &RunnerError::UnknownStepId(id) => write!(f, "No step is registered with id [{}]", id),
+      &RunnerError::StepTimedOut { timeout, .. } => {
+        write!(f, "Step timed out after {}ms", duration_ms(timeout))
+      },
+      &RunnerError::Busy => {
+        write!(f,
+               "A timed out step is still running, so no further requests can be handled")
+      },
+    }
+  }
+}
+
+impl From<RunnerError> for FailMessage {
+  fn from(err: RunnerError) -> FailMessage {
+    FailMessage::with_exception(err.to_string(), "cucumber::RunnerError".to_owned())
+  }
+}
+
+impl<World: Send + 'static> WorldRunner<World> {
+  fn invoke(&mut self,
+            id: StepId,
+            args: Vec<InvokeArgument>)
+            -> Result<InvokeResponse, RunnerError> {
+    if self.cuke.step(id).is_none() {
+      return Err(RunnerError::UnknownStepId(id));
+    }
+
+    let start = Instant::now();
+    let response = match self.cuke.step_timeout(id).or(self.step_timeout) {
+      None => {
+        let step = self.cuke.step(id).unwrap();
+        Ok(invoke_to_response(step, &self.cuke, self.world.as_mut().unwrap(), args))
+      },
+      Some(timeout) => self.invoke_with_timeout(id, args, timeout),
+    };
+
+    self.usage.record_invoke(id, start.elapsed());
+    response
+  }
+
+  fn invoke_with_timeout(&mut self,
+                         id: StepId,
+                         args: Vec<InvokeArgument>,
+                         timeout: Duration)
+                         -> Result<InvokeResponse, RunnerError> {
+    // The World travels to the worker thread, and only comes back if the step
+    // finishes. The steps stay here as well, so that they can still be matched.
+    let mut cuke = self.cuke.share();
+    cuke.set_tags(self.cuke.tags().clone());
+    let mut world = self.world.take().unwrap();
+    let (tx, rx) = channel();
+
+    thread::spawn(move || {
+      let response = invoke_to_response(cuke.step(id).unwrap(), &cuke, &mut world, args);
+      let _ = tx.send((world, response));
+    });
+
+    match rx.recv_timeout(timeout) {
+      Ok((world, response)) => {
+        self.world = Some(world);
+        Ok(response)
+      },
+      Err(_) => {
+        self.timed_out = Some(rx);
+        Err(RunnerError::StepTimedOut {
+          id: id,
+          timeout: timeout,
+        })
+      },
+    }
+  }
+
+  /// Take back the World from a timed out step if it has since finished,
+  /// returning whether the World is there to run steps with
+  fn reclaim(&mut self) -> bool {
+    let finished = match self.timed_out {
+      Some(ref rx) => rx.try_recv().ok(),
+      None => None,
+    };
+
+    if let Some((world, _)) = finished {
+      self.world = Some(world);
+      self.timed_out = None;
+    }
+
+    self.world.is_some()
+  }
+}
+
+impl<World> WorldRunner<World> {
+  // Runs the hooks of a kind for the current scenario, failing with the first
+  // of them that does not pass
+  //
+  // NOTE: As in native runs, every After hook runs, while the Before hooks
+  // after one that did not pass are skipped
+  fn run_hooks(&mut self, kind: HookKind) -> Result<(), FailMessage> {
+    let cuke = &self.cuke;
+    let world = self.world.as_mut().unwrap();
+    let mut failure = None;
+
+    for (source, hook) in cuke.hooks(kind) {
+      let message = match catch_to_response(|| hook(cuke, world)) {
+        InvokeResponse::Success => continue,
+        InvokeResponse::Fail(message) => message,
+        InvokeResponse::Pending(message) => FailMessage::new(message),
+      };
+      if failure.is_none() {
+        failure = Some(FailMessage::new(format!("{} hook at {} failed: {}",
+                                                kind.as_str(),
+                                                source,
+                                                message.message())));
+      }
+      if kind == HookKind::Before {
+        break;
+      }
     }
+
+    failure.map_or(Ok(()), Err)
   }
 }
 
+fn duration_ms(duration: Duration) -> u64 {
+  duration.as_secs() * 1000 + (duration.subsec_nanos() / 1000000) as u64
+}
+
 /// An interface for implementers that can consume a
 /// [Request](../event/request/enum.Request.html) and yield a
 /// [Response](../event/response/enum.Response.html)
@@ -54,21 +254,41 @@ impl<T: Fn(Request) -> Response> CommandRunner for T {
   }
 }
 
-impl<World> CommandRunner for WorldRunner<World> {
+impl<World: Send + 'static> CommandRunner for WorldRunner<World> {
   fn execute_cmd(&mut self, req: Request) -> Response {
+    // NOTE: Matching steps and writing snippets never touch the World
+    if !self.reclaim() {
+      match req {
+        Request::Invoke(_) => {
+          return Response::Invoke(InvokeResponse::Fail(RunnerError::Busy.into()))
+        },
+        Request::BeginScenario(_) |
+        Request::EndScenario(_) => return Response::Fail(RunnerError::Busy.into()),
+        Request::StepMatches(_) |
+        Request::SnippetText(_) => {},
+      }
+    }
+
     match req {
       Request::BeginScenario(params) => {
-        self.cuke.tags = params.tags;
-        Response::BeginScenario
+        self.cuke.set_tags(Tags::from_wire(params.tags));
+        match self.run_hooks(HookKind::Before) {
+          Ok(()) => Response::BeginScenario,
+          Err(message) => Response::Fail(message),
+        }
       },
       Request::Invoke(params) => {
-        let step = self.cuke
-          .step(u32::from_str(&params.id).unwrap())
-          .unwrap();
-        Response::Invoke(invoke_to_response(step, &self.cuke, &mut self.world, params.args))
+        let response = match StepId::from_str(&params.id) {
+          Ok(id) => self.invoke(id, params.args),
+          Err(_) => Err(RunnerError::InvalidStepId(params.id)),
+        };
+        Response::Invoke(response.unwrap_or_else(|err| InvokeResponse::Fail(err.into())))
       },
       Request::StepMatches(params) => {
         let matches = self.cuke.find_match(&params.name_to_match);
+        for id in matches.iter().filter_map(|step| StepId::from_str(&step.id).ok()) {
+          self.usage.record_match(id, &params.name_to_match);
+        }
         if matches.len() == 0 {
           Response::StepMatches(StepMatchesResponse::NoMatch)
         } else {
@@ -76,19 +296,18 @@ impl<World> CommandRunner for WorldRunner<World> {
         }
       },
       Request::EndScenario(_) => {
-        self.cuke.tags = Vec::new();
-        Response::EndScenario
+        let hooks = self.run_hooks(HookKind::After);
+        self.cuke.set_tags(Tags::new());
+        match hooks {
+          Ok(()) => Response::EndScenario,
+          Err(message) => Response::Fail(message),
+        }
       },
-      // TODO: For some reason, cucumber prints the ruby snippet too. Fix that
       Request::SnippetText(params) => {
-        let text = format!("  // In a step registration block where cuke: &mut \
-                            CucumberRegistrar<YourWorld>\n  use cucumber::InvokeResponse;\n  use \
-                            cucumber::helpers::r;\n  {}!(cuke, r(\"^{}$\"), Box::new(move |c, _, \
-                            _| {{\n    c.pending(\"TODO\")\n  }}));",
-                           params.step_keyword,
-                           params.step_name);
-
-        Response::SnippetText(text)
+        Response::SnippetText(snippet::snippet_text(self.snippet_style,
+                                                    &params.step_keyword,
+                                                    &params.step_name,
+                                                    &params.multiline_arg_class))
       },
     }
   }
@@ -106,6 +325,26 @@ impl<World> CucumberRegistrar<World> for WorldRunner<World> {
   fn then(&mut self, file: &str, line: u32, regex: Regex, step: SimpleStep<World>) {
     self.cuke.then(file, line, regex, step)
   }
+
+  fn timeout(&mut self, regex: &str, timeout: Duration) {
+    self.cuke.timeout(regex, timeout)
+  }
+
+  fn checks_arguments(&mut self, regex: &str) {
+    self.cuke.checks_arguments(regex)
+  }
+
+  fn before(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.cuke.before(file, line, tags, hook)
+  }
+
+  fn after(&mut self, file: &str, line: u32, tags: Option<TagExpression>, hook: Hook<World>) {
+    self.cuke.after(file, line, tags, hook)
+  }
+
+  fn after_all(&mut self, file: &str, line: u32, hook: RunHook<World>) {
+    self.cuke.after_all(file, line, hook)
+  }
 }
 
 pub fn invoke_to_response<World>(test_body: &SimpleStep<World>,
@@ -113,7 +352,45 @@ pub fn invoke_to_response<World>(test_body: &SimpleStep<World>,
                                  world: &mut World,
                                  args: Vec<InvokeArgument>)
                                  -> InvokeResponse {
-  let result = panic::catch_unwind(AssertUnwindSafe(|| test_body(cuke, world, args)));
+  catch_to_response(|| test_body(cuke, world, args))
+}
+
+thread_local!(static CATCHING: Cell<bool> = Cell::new(false));
+
+/// Keep the panics of steps and hooks run by
+/// [catch_to_response](fn.catch_to_response.html) from being printed, as they
+/// are reported as results, while any other panic still goes to the panic
+/// hook set before
+pub fn silence_step_panics() {
+  let previous = panic::take_hook();
+  panic::set_hook(Box::new(move |info| if !CATCHING.with(|catching| catching.get()) {
+    previous(info)
+  }));
+}
+
+/// Run every hook for the end of a run, returning the messages of those that
+/// failed
+pub fn run_after_all_hooks<World>(cuke: &Cucumber<World>) -> Vec<String> {
+  cuke.after_all_hooks()
+    .into_iter()
+    .filter_map(|(source, hook)| {
+      let message = match catch_to_response(|| hook(cuke)) {
+        InvokeResponse::Success => return None,
+        InvokeResponse::Fail(message) => message.message().to_owned(),
+        InvokeResponse::Pending(message) => message,
+      };
+      Some(format!("AfterAll hook at {} failed: {}", source, message))
+    })
+    .collect()
+}
+
+/// Run a step or hook, turning a panic into the failure or pending response
+/// it was raised with
+pub fn catch_to_response<F: FnOnce()>(body: F) -> InvokeResponse {
+  // NOTE: Catching may be nested, so it is restored rather than ended
+  let was_catching = CATCHING.with(|flag| flag.replace(true));
+  let result = panic::catch_unwind(AssertUnwindSafe(body));
+  CATCHING.with(|flag| flag.set(was_catching));
   match result {
     Ok(()) => InvokeResponse::Success,
     Err(err) => {
@@ -136,3 +413,181 @@ pub fn invoke_to_response<World>(test_body: &SimpleStep<World>,
     },
   }
 }
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use std::sync::Mutex;
+  use std::sync::mpsc::channel;
+  use std::thread;
+  use std::time::Duration;
+
+  use cucumber_regex;
+  use definitions::registration::CucumberRegistrar;
+  use event::request::{BeginScenarioRequest, EndScenarioRequest, InvokeRequest, Request,
+                       StepMatchesRequest};
+  use event::response::{InvokeResponse, Response, StepMatchesResponse};
+
+  fn invoke(id: &str) -> Request {
+    Request::Invoke(InvokeRequest {
+      id: id.to_owned(),
+      args: Vec::new(),
+    })
+  }
+
+  #[test]
+  fn it_fails_steps_exceeding_their_timeout() {
+    // NOTE: The slow step only returns once released, which is after it timed
+    // out, so timing out cannot depend on how busy the machine is
+    let (release, released) = channel::<()>();
+    let released = Mutex::new(released);
+    let mut runner = WorldRunner::new(0u32);
+    runner.given("file",
+                 1,
+                 cucumber_regex::build("^slow$"),
+                 Box::new(move |_, world: &mut u32, _| {
+                   let _ = released.lock().unwrap().recv();
+                   *world += 1;
+                 }));
+    runner.given("file", 2, cucumber_regex::build("^fast$"), Box::new(|_, world: &mut u32, _| {
+      assert_eq!(*world, 1);
+    }));
+    runner.timeout("^slow$", Duration::from_millis(20));
+
+    let timed_out = RunnerError::StepTimedOut {
+      id: 0,
+      timeout: Duration::from_millis(20),
+    };
+    assert_eq!(runner.execute_cmd(invoke("0")),
+               Response::Invoke(InvokeResponse::Fail(timed_out.into())));
+
+    // The World is unavailable until the slow step returns it
+    assert_eq!(runner.execute_cmd(invoke("1")),
+               Response::Invoke(InvokeResponse::Fail(RunnerError::Busy.into())));
+    assert_eq!(runner.execute_cmd(Request::BeginScenario(BeginScenarioRequest {
+                 tags: Vec::new(),
+               })),
+               Response::Fail(RunnerError::Busy.into()));
+    // Steps can still be matched, as that does not need the World
+    match runner.execute_cmd(Request::StepMatches(StepMatchesRequest {
+      name_to_match: "fast".to_owned(),
+    })) {
+      Response::StepMatches(StepMatchesResponse::Match(steps)) => assert_eq!(steps[0].id, "1"),
+      other => panic!("Expected a match, but got {:?}", other),
+    }
+
+    release.send(()).unwrap();
+    let busy = Response::Invoke(InvokeResponse::Fail(RunnerError::Busy.into()));
+    // The World is back once the slow step has returned it
+    let mut response = runner.execute_cmd(invoke("1"));
+    while response == busy {
+      thread::yield_now();
+      response = runner.execute_cmd(invoke("1"));
+    }
+    assert_eq!(response, Response::Invoke(InvokeResponse::Success));
+  }
+
+  #[test]
+  fn it_runs_hooks_around_wire_scenarios() {
+    let mut runner = WorldRunner::new(0u32);
+    runner.before("hooks", 1, None, Box::new(|_, world: &mut u32| *world = 10));
+    runner.before("hooks",
+                  2,
+                  Some(TagExpression::parse("@broken").unwrap()),
+                  Box::new(|c, _| c.fail("no database")));
+    runner.after("hooks", 3, None, Box::new(|_, world: &mut u32| *world += 1));
+    runner.given("file", 1, cucumber_regex::build("^eleven$"), Box::new(|_, world: &mut u32, _| {
+      assert_eq!(*world, 11);
+    }));
+    let begin = |tags: &[&str]| {
+      Request::BeginScenario(BeginScenarioRequest {
+        tags: tags.iter().map(|tag| tag.to_string()).collect(),
+      })
+    };
+    let end = || Request::EndScenario(EndScenarioRequest { tags: Vec::new() });
+
+    assert_eq!(runner.execute_cmd(begin(&[])), Response::BeginScenario);
+    assert_eq!(runner.execute_cmd(end()), Response::EndScenario);
+    assert_eq!(runner.execute_cmd(invoke("0")),
+               Response::Invoke(InvokeResponse::Success));
+
+    match runner.execute_cmd(begin(&["broken"])) {
+      Response::Fail(message) => {
+        assert_eq!(message.message(), "Before hook at hooks:2 failed: no database")
+      },
+      other => panic!("Expected a failed hook, but got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn it_only_marks_panics_as_caught_while_catching() {
+    let inside = catch_to_response(|| {
+      assert!(CATCHING.with(|flag| flag.get()));
+      catch_to_response(|| ());
+      assert!(CATCHING.with(|flag| flag.get()));
+    });
+    assert_eq!(inside, InvokeResponse::Success);
+    assert!(!CATCHING.with(|flag| flag.get()));
+  }
+
+  #[test]
+  fn it_fails_invalid_step_ids() {
+    let mut runner = WorldRunner::new(0u32);
+
+    match runner.execute_cmd(invoke("not a number")) {
+      Response::Invoke(InvokeResponse::Fail(message)) => {
+        assert_eq!(message.message(), "Step id [not a number] is not a valid id");
+        assert_eq!(message.exception(), "cucumber::RunnerError");
+      },
+      other => panic!("Expected a failed invoke, but got {:?}", other),
+    }
+  }
+
+  #[test]
+  fn it_fails_unknown_step_ids() {
+    let mut runner = WorldRunner::new(0u32);
+    runner.given("file", 1, cucumber_regex::build("^known$"), Box::new(|_, _, _| ()));
+
+    assert_eq!(runner.execute_cmd(invoke("1")),
+               Response::Invoke(InvokeResponse::Fail(RunnerError::UnknownStepId(1).into())));
+    assert_eq!(runner.execute_cmd(invoke("0")),
+               Response::Invoke(InvokeResponse::Success));
+  }
+
+  #[test]
+  fn it_tracks_step_usage() {
+    let mut runner = WorldRunner::new(0u32);
+    runner.given("file", 1, cucumber_regex::build("^used$"), Box::new(|_, _, _| ()));
+    runner.given("file", 2, cucumber_regex::build("^unused$"), Box::new(|_, _, _| ()));
+    let usage = runner.usage();
+
+    runner.execute_cmd(Request::StepMatches(StepMatchesRequest {
+      name_to_match: "used".to_owned(),
+    }));
+    runner.execute_cmd(invoke("0"));
+
+    let stats = usage.stats(0);
+    assert_eq!(stats.matched, 1);
+    assert_eq!(stats.invoked, 1);
+    assert_eq!(stats.slowest[0].step, "used");
+    assert_eq!(usage.unused(runner.cucumber().definitions())
+                 .into_iter()
+                 .map(|def| def.source)
+                 .collect::<Vec<_>>(),
+               vec!["file:2".to_owned()]);
+  }
+
+  #[test]
+  fn it_applies_the_global_timeout() {
+    let mut runner = WorldRunner::new(0u32).step_timeout(Duration::from_millis(500));
+    runner.given("file", 1, cucumber_regex::build("^quick$"), Box::new(|_, world: &mut u32, _| {
+      *world += 1;
+    }));
+
+    assert_eq!(runner.execute_cmd(invoke("0")),
+               Response::Invoke(InvokeResponse::Success));
+    assert_eq!(runner.execute_cmd(invoke("0")),
+               Response::Invoke(InvokeResponse::Success));
+  }
+}
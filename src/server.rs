@@ -1,4 +1,4 @@
-use std::io::{BufRead, BufReader, Write};
+use std::io::{self, BufRead, BufReader, Write};
 use std::sync::mpsc::{Sender, TryRecvError, channel};
 use std::sync::{Arc, Barrier};
 use std::time::Duration;
@@ -8,6 +8,7 @@ use std::net::TcpListener;
 use runner::CommandRunner;
 
 use event::request::Request;
+use event::response::{FailMessage, Response};
 
 use serde_json;
 
@@ -47,6 +48,7 @@ use serde_json;
 #[allow(dead_code)]
 pub struct Server<R: CommandRunner + Send> {
   runner: R,
+  log_malformed: bool,
 }
 
 /// An "unplugged" return value from server start, for compiler reasons
@@ -86,7 +88,20 @@ impl ServerHandle {
 impl<R: CommandRunner + Send> Server<R> {
   #[allow(dead_code)]
   pub fn new(runner: R) -> Server<R> {
-    Server { runner: runner }
+    Server {
+      runner: runner,
+      log_malformed: false,
+    }
+  }
+
+  /// Print the raw text of requests that could not be parsed to stderr
+  ///
+  /// Malformed requests are always answered with a protocol level `fail`
+  /// response, this only controls whether the offending line is logged too.
+  #[allow(dead_code)]
+  pub fn log_malformed(mut self, enabled: bool) -> Server<R> {
+    self.log_malformed = enabled;
+    self
   }
 
   #[allow(dead_code)]
@@ -122,17 +137,20 @@ impl<R: CommandRunner + Send> Server<R> {
               if size == 0 {
                 running = false
               } else {
-                let request = serde_json::from_str::<Request>(&body);
-
-                match request {
-                  Ok(req_body) => {
-                    let response = self.runner.execute_cmd(req_body);
-                    let _ =
-                      stream.write(format!("{}\n", serde_json::to_string(&response).unwrap())
-                        .as_bytes());
+                let response = match serde_json::from_str::<Request>(&body) {
+                  Ok(req_body) => self.runner.execute_cmd(req_body),
+                  Err(err) => {
+                    if self.log_malformed {
+                      let _ = writeln!(io::stderr(),
+                                       "Malformed request from wire: {}",
+                                       body.trim_right());
+                    }
+                    Response::Fail(FailMessage::new(format!("Malformed request: {}", err)))
                   },
-                  _ => {},
-                }
+                };
+
+                let _ = stream.write(format!("{}\n", serde_json::to_string(&response).unwrap())
+                  .as_bytes());
               }
             });
           },
@@ -226,4 +244,39 @@ mod test {
     stop_tx.send(()).unwrap();
     handle.join().unwrap();
   }
+
+  #[test]
+  fn it_fails_malformed_requests() {
+    let server = Server::new(|_| Response::BeginScenario);
+    let (handle, stop_tx) = server.start(Some("127.0.0.1:1236"));
+    let mut stream = TcpStream::connect("127.0.0.1:1236").unwrap();
+
+    {
+      stream.write(b"[\"begin_scenario\"\n").unwrap();
+      let mut buffered_reader = BufReader::new(stream.try_clone().unwrap());
+      let mut body = String::new();
+      buffered_reader.read_line(&mut body).unwrap();
+      assert!(body.starts_with("[\"fail\",{\"message\":\"Malformed request: "));
+    }
+
+    {
+      stream.write(b"[\"not_a_command\"]\n").unwrap();
+      let mut buffered_reader = BufReader::new(stream.try_clone().unwrap());
+      let mut body = String::new();
+      buffered_reader.read_line(&mut body).unwrap();
+      assert!(body.starts_with("[\"fail\",{\"message\":\"Malformed request: "));
+    }
+
+    {
+      // The server keeps serving after a malformed request
+      stream.write(b"[\"begin_scenario\"]\n").unwrap();
+      let mut buffered_reader = BufReader::new(stream.try_clone().unwrap());
+      let mut body = String::new();
+      buffered_reader.read_line(&mut body).unwrap();
+      assert_eq!(body, "[\"success\"]\n");
+    }
+
+    stop_tx.send(()).unwrap();
+    handle.join().unwrap();
+  }
 }
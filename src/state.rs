@@ -1,12 +1,17 @@
 pub use regex::{Captures, Regex};
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::slice;
+use std::sync::Arc;
+use std::time::Duration;
 
 use event::response::InvokeResponse;
 use event::response::StepArg;
 use event::response::Step as ResponseStep;
 use event::request::InvokeArgument;
-use definitions::registration::SimpleStep;
+use definitions::registration::{Hook, HookKind, RunHook, SimpleStep, StepKeyword};
+use definitions::listing::StepDefinition;
+use tags::{TagExpression, Tags};
 
 /// The trait steps must implement to be invokable
 ///
@@ -60,36 +65,207 @@ pub type StepId = u32;
 /// ```
 ///
 pub struct Cucumber<World> {
+  registry: Arc<Registry<World>>,
+  tags: Tags,
+  dry_run: bool,
+}
+
+// The steps and hooks of a Cucumber, which the threads of a parallel run share
+struct Registry<World> {
   step_regexes: Vec<Regex>,
   step_ids: HashMap<String, (StepId, String)>,
   steps: HashMap<StepId, SimpleStep<World>>,
-  pub tags: Vec<String>,
+  timeouts: HashMap<StepId, Duration>,
+  checks_arguments: HashSet<StepId>,
+  keywords: HashMap<StepId, StepKeyword>,
+  hooks: Vec<(HookKind, String, Option<TagExpression>, Hook<World>)>,
+  after_all_hooks: Vec<(String, RunHook<World>)>,
 }
 
 impl<World> Cucumber<World> {
   pub fn new() -> Cucumber<World> {
     Cucumber {
-      step_regexes: Vec::new(),
-      step_ids: HashMap::new(),
-      steps: HashMap::new(),
-      tags: Vec::new(),
+      registry: Arc::new(Registry {
+        step_regexes: Vec::new(),
+        step_ids: HashMap::new(),
+        steps: HashMap::new(),
+        timeouts: HashMap::new(),
+        checks_arguments: HashSet::new(),
+        keywords: HashMap::new(),
+        hooks: Vec::new(),
+        after_all_hooks: Vec::new(),
+      }),
+      tags: Tags::new(),
+      dry_run: false,
     }
   }
 
+  /// A Cucumber with the same steps, hooks and dry run setting, but no tags,
+  /// for running scenarios on another thread
+  ///
+  /// Steps and hooks cannot be added to any of them until the others are
+  /// dropped, so this is only for the runners.
+  #[doc(hidden)]
+  pub fn share(&self) -> Cucumber<World> {
+    Cucumber {
+      registry: self.registry.clone(),
+      tags: Tags::new(),
+      dry_run: self.dry_run,
+    }
+  }
+
+  // NOTE: Runners only share their Cucumber while they run, or with the thread
+  // of a timed step that may outlive it in a wire run, and steps are never
+  // added once they are running, so this can only fail when sharing by hand
+  fn registry_mut(&mut self) -> &mut Registry<World> {
+    Arc::get_mut(&mut self.registry).expect("Steps cannot be added to a shared Cucumber")
+  }
+
   /// Add a new step to the set of steps.
   ///
   /// This method is typically executed by a
   /// [WorldRunner](../runner/struct.WorldRunner.html) when
   /// `#given`, `#when` or `#then` methods are called.
-  pub fn insert_step(&mut self, path: String, regex: Regex, step: SimpleStep<World>) {
+  pub fn insert_step(&mut self, path: String, regex: Regex, step: SimpleStep<World>) -> StepId {
+    let registry = self.registry_mut();
     let str_rep = regex.as_str().to_owned();
-    self.step_regexes.push(regex);
+    registry.step_regexes.push(regex);
 
-    let this_id = self.step_ids.values().max().map(|&(ref res, _)| res + 1).unwrap_or(0);
+    let this_id = registry.step_ids.values().max().map(|&(ref res, _)| res + 1).unwrap_or(0);
     // TODO: handle existing str_reps in hash
-    self.step_ids.insert(str_rep, (this_id.clone(), path));
+    registry.step_ids.insert(str_rep, (this_id.clone(), path));
+
+    registry.steps.insert(this_id, step);
+    this_id
+  }
+
+  /// Add a new step to the set of steps, remembering the keyword it was
+  /// registered with.
+  pub fn insert_keyword_step(&mut self,
+                             keyword: StepKeyword,
+                             path: String,
+                             regex: Regex,
+                             step: SimpleStep<World>)
+                             -> StepId {
+    let id = self.insert_step(path, regex, step);
+    self.registry_mut().keywords.insert(id, keyword);
+    id
+  }
+
+  /// The tags of the current scenario, which are empty between scenarios
+  pub fn tags(&self) -> &Tags {
+    &self.tags
+  }
+
+  /// Set the tags of the scenario that is starting
+  ///
+  /// This is done by the runners when a scenario begins and ends.
+  pub fn set_tags(&mut self, tags: Tags) {
+    self.tags = tags;
+  }
+
+  /// Whether steps are only being matched and given their arguments, as in
+  /// the dry runs of a [NativeRunner](../native/struct.NativeRunner.html)
+  ///
+  /// Steps added with the step macros then convert their arguments without
+  /// running their bodies. Steps added otherwise are not invoked at all.
+  pub fn dry_run(&self) -> bool {
+    self.dry_run
+  }
+
+  /// Set whether steps are invoked for a dry run
+  pub fn set_dry_run(&mut self, enabled: bool) {
+    self.dry_run = enabled;
+  }
+
+  /// Add a hook, run around scenarios by a
+  /// [NativeRunner](../native/struct.NativeRunner.html), limited to those
+  /// whose tags match the expression if one is given
+  pub fn insert_hook(&mut self,
+                     kind: HookKind,
+                     path: String,
+                     tags: Option<TagExpression>,
+                     hook: Hook<World>) {
+    self.registry_mut().hooks.push((kind, path, tags, hook));
+  }
+
+  /// The hooks of a kind for the current scenario, with where they were
+  /// defined, in the order they run
+  ///
+  /// Before hooks run in the order they were registered, and After hooks in
+  /// the reverse order.
+  pub fn hooks(&self, kind: HookKind) -> Vec<(&str, &Hook<World>)> {
+    let mut hooks: Vec<(&str, &Hook<World>)> = self.registry
+      .hooks
+      .iter()
+      .filter(|&&(hook_kind, _, ref expression, _)| {
+        hook_kind == kind && expression.as_ref().map_or(true, |e| self.tags.matches(e))
+      })
+      .map(|&(_, ref path, _, ref hook)| (path.as_str(), hook))
+      .collect();
+    if kind == HookKind::After {
+      hooks.reverse();
+    }
+    hooks
+  }
+
+  /// Add a hook, run once a run is over
+  pub fn insert_after_all_hook(&mut self, path: String, hook: RunHook<World>) {
+    self.registry_mut().after_all_hooks.push((path, hook));
+  }
+
+  /// The hooks run once a run is over, with where they were defined, in the
+  /// reverse of the order they were registered, as for After hooks
+  pub fn after_all_hooks(&self) -> Vec<(&str, &RunHook<World>)> {
+    self.registry
+      .after_all_hooks
+      .iter()
+      .rev()
+      .map(|&(ref path, ref hook)| (path.as_str(), hook))
+      .collect()
+  }
+
+  /// Iterate over the registered step definitions, in registration order
+  pub fn definitions(&self) -> Definitions<World> {
+    Definitions {
+      cuke: self,
+      regexes: self.registry.step_regexes.iter(),
+    }
+  }
+
+  /// Limit how long the step registered with a regular expression may run
+  ///
+  /// This method is typically executed by a
+  /// [WorldRunner](../runner/struct.WorldRunner.html) when the `timeout`
+  /// form of the step macros is used. Unknown regular expressions are ignored.
+  pub fn set_timeout(&mut self, regex: &str, timeout: Duration) {
+    let registry = self.registry_mut();
+    if let Some(&(id, _)) = registry.step_ids.get(regex) {
+      registry.timeouts.insert(id, timeout);
+    }
+  }
 
-    self.steps.insert(this_id, step);
+  /// Retrieve the timeout registered for a step, if any
+  pub fn step_timeout(&self, id: StepId) -> Option<Duration> {
+    self.registry.timeouts.get(&id).cloned()
+  }
+
+  /// Mark the step registered with a regular expression as only checking its
+  /// arguments in a dry run, so that dry runs may invoke it
+  ///
+  /// This method is typically executed by a
+  /// [WorldRunner](../runner/struct.WorldRunner.html) for steps added with the
+  /// step macros. Unknown regular expressions are ignored.
+  pub fn set_checks_arguments(&mut self, regex: &str) {
+    let registry = self.registry_mut();
+    if let Some(&(id, _)) = registry.step_ids.get(regex) {
+      registry.checks_arguments.insert(id);
+    }
+  }
+
+  /// Whether a step only checks its arguments in a dry run
+  pub fn step_checks_arguments(&self, id: StepId) -> bool {
+    self.registry.checks_arguments.contains(&id)
   }
 
   /// Find a step or steps matching a given string.
@@ -99,7 +275,9 @@ impl<World> Cucumber<World> {
   /// trying to find a step corresponding to a string provided by the
   /// [Server](../server/struct.Server.html).
   pub fn find_match(&self, str: &str) -> Vec<ResponseStep> {
-    self.step_regexes.iter()
+    self.registry
+      .step_regexes
+      .iter()
       .filter_map(|ref regex| {
         // Get captures from regex
         regex.captures(str).map(|captures| {
@@ -113,7 +291,7 @@ impl<World> Cucumber<World> {
                 StepArg { pos: pos, val: captures.at(idx).map(|v| v.to_owned()) }
               })
               .collect();
-          let (id, path) = self.step_ids.get(regex.as_str()).unwrap().clone();
+          let (id, path) = self.registry.step_ids.get(regex.as_str()).unwrap().clone();
           ResponseStep {id: id.to_string(), args: captures, source: path }
         })
       })
@@ -175,14 +353,41 @@ impl<World> Cucumber<World> {
   /// a request specifically invokes a step by Id. This is typical of the
   /// Cucumber Wire protocol.
   pub fn step(&self, id: StepId) -> Option<&SimpleStep<World>> {
-    self.steps.get(&id)
+    self.registry.steps.get(&id)
+  }
+}
+
+/// An iterator over the step definitions of a
+/// [Cucumber](./struct.Cucumber.html)
+pub struct Definitions<'a, World: 'a> {
+  cuke: &'a Cucumber<World>,
+  regexes: slice::Iter<'a, Regex>,
+}
+
+impl<'a, World> Iterator for Definitions<'a, World> {
+  type Item = StepDefinition;
+
+  fn next(&mut self) -> Option<StepDefinition> {
+    self.regexes.next().map(|regex| {
+      let &(id, ref source) = self.cuke.registry.step_ids.get(regex.as_str()).unwrap();
+      StepDefinition {
+        id: id,
+        keyword: self.cuke.registry.keywords.get(&id).cloned(),
+        pattern: regex.as_str().to_owned(),
+        source: source.clone(),
+        arity: regex.captures_len() - 1,
+      }
+    })
   }
 }
 
 #[cfg(test)]
 mod test {
   use super::*;
+  use std::time::Duration;
   use cucumber_regex as regex;
+  use definitions::listing::StepDefinition;
+  use definitions::registration::StepKeyword;
   use event::response::StepArg;
   use event::response::Step as ResponseStep;
 
@@ -244,6 +449,69 @@ mod test {
     cucumber.invoke("example", &mut world, None)
   }
 
+  #[test]
+  fn cuke_lists_definitions() {
+    type World = u32;
+
+    let mut cucumber: Cucumber<World> = Cucumber::new();
+    cucumber.insert_step("file:1".to_owned(),
+                         regex::build("^example$"),
+                         Box::new(|_, _, _| ()));
+    cucumber.insert_keyword_step(StepKeyword::Then,
+                                 "file:2".to_owned(),
+                                 regex::build("^example( stuff)? (\\d+)$"),
+                                 Box::new(|_, _, _| ()));
+
+    assert_eq!(cucumber.definitions().collect::<Vec<_>>(),
+               vec![StepDefinition {
+                      id: 0,
+                      keyword: None,
+                      pattern: "^example$".to_owned(),
+                      source: "file:1".to_owned(),
+                      arity: 0,
+                    },
+                    StepDefinition {
+                      id: 1,
+                      keyword: Some(StepKeyword::Then),
+                      pattern: "^example( stuff)? (\\d+)$".to_owned(),
+                      source: "file:2".to_owned(),
+                      arity: 2,
+                    }]);
+  }
+
+  #[test]
+  fn cuke_sets_step_timeouts() {
+    type World = u32;
+
+    let mut cucumber: Cucumber<World> = Cucumber::new();
+    cucumber.insert_step("file:line".to_owned(),
+                         regex::build("^example$"),
+                         Box::new(|_, _, _| ()));
+    cucumber.set_timeout("^example$", Duration::from_millis(50));
+    cucumber.set_timeout("^unknown$", Duration::from_millis(50));
+
+    assert_eq!(cucumber.step_timeout(0), Some(Duration::from_millis(50)));
+    assert_eq!(cucumber.step_timeout(1), None);
+  }
+
+  #[test]
+  fn cuke_marks_steps_checking_arguments() {
+    type World = u32;
+
+    let mut cucumber: Cucumber<World> = Cucumber::new();
+    cucumber.insert_step("file:line".to_owned(),
+                         regex::build("^example$"),
+                         Box::new(|_, _, _| ()));
+    cucumber.insert_step("file:line".to_owned(),
+                         regex::build("^other$"),
+                         Box::new(|_, _, _| ()));
+    cucumber.set_checks_arguments("^example$");
+    cucumber.set_checks_arguments("^unknown$");
+
+    assert!(cucumber.step_checks_arguments(0));
+    assert!(!cucumber.step_checks_arguments(1));
+  }
+
   #[test]
   fn find_match_optional_args_work() {
     type World = u32;
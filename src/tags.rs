@@ -0,0 +1,423 @@
+//! The tags of scenarios, and tag expressions such as
+//! `@smoke and not (@slow or @wip)`
+
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use gherkin::{Feature, Scenario};
+
+/// Where a tag was written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagLevel {
+  Feature,
+  Rule,
+  Scenario,
+  /// The examples of a scenario outline
+  Examples,
+}
+
+/// The tags of the current scenario, including those it inherits from its
+/// feature, rule and examples
+///
+/// Tags always start with `@`, whether or not the client that sent them
+/// included it.
+///
+/// # Example
+///
+/// ```
+/// use cucumber::tags::{TagExpression, Tags};
+///
+/// let tags = Tags::from_wire(vec!["db".to_owned(), "@slow".to_owned()]);
+/// assert!(tags.contains("@db"));
+/// assert!(tags.contains("slow"));
+/// assert!(tags.matches(&TagExpression::parse("@db and not @wip").unwrap()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Tags {
+  tags: Vec<(TagLevel, String)>,
+}
+
+fn with_at(tag: &str) -> String {
+  if tag.starts_with('@') {
+    tag.to_owned()
+  } else {
+    format!("@{}", tag)
+  }
+}
+
+impl Tags {
+  /// No tags, as outside of a scenario
+  pub fn new() -> Tags {
+    Tags::default()
+  }
+
+  /// The tags of a scenario of a feature, at the level they were written
+  pub fn of_scenario(feature: &Feature, scenario: &Scenario) -> Tags {
+    let mut tags = Tags::new();
+    tags.add(TagLevel::Feature, &feature.tags);
+    tags.add(TagLevel::Rule, &scenario.rule_tags);
+    tags.add(TagLevel::Scenario, &scenario.tags);
+    tags.add(TagLevel::Examples, &scenario.examples_tags);
+    tags
+  }
+
+  /// Tags sent by a wire client
+  ///
+  /// The wire protocol does not say where tags were written, so they are all
+  /// taken to be the scenario's.
+  pub fn from_wire(names: Vec<String>) -> Tags {
+    let mut tags = Tags::new();
+    tags.add(TagLevel::Scenario, &names);
+    tags
+  }
+
+  fn add(&mut self, level: TagLevel, tags: &[String]) {
+    self.tags.extend(tags.iter().map(|tag| (level, with_at(tag))));
+  }
+
+  /// Whether a tag applies, with or without its leading `@`
+  pub fn contains(&self, tag: &str) -> bool {
+    let tag = with_at(tag);
+    self.tags.iter().any(|&(_, ref name)| *name == tag)
+  }
+
+  /// Whether the tags match a tag expression
+  pub fn matches(&self, expression: &TagExpression) -> bool {
+    expression.matches(&self.names())
+  }
+
+  /// Every tag, from the feature's down to the examples'
+  pub fn names(&self) -> Vec<String> {
+    self.tags.iter().map(|&(_, ref name)| name.clone()).collect()
+  }
+
+  /// The tags written at one level
+  pub fn at(&self, level: TagLevel) -> Vec<&str> {
+    self.tags
+      .iter()
+      .filter(|&&(tag_level, _)| tag_level == level)
+      .map(|&(_, ref name)| name.as_str())
+      .collect()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tags.is_empty()
+  }
+}
+
+/// A boolean expression over the tags of a scenario
+///
+/// `not` binds tighter than `and`, which binds tighter than `or`, and
+/// parentheses group. Tags start with `@`, and a backslash escapes a space or
+/// parenthesis in a tag.
+///
+/// # Example
+///
+/// ```
+/// use cucumber::tags::TagExpression;
+///
+/// let expression = TagExpression::parse("@smoke and not (@slow or @wip)").unwrap();
+/// assert!(expression.matches(&["@smoke".to_owned()]));
+/// assert!(!expression.matches(&["@smoke".to_owned(), "@wip".to_owned()]));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TagExpression {
+  Tag(String),
+  Not(Box<TagExpression>),
+  And(Box<TagExpression>, Box<TagExpression>),
+  Or(Box<TagExpression>, Box<TagExpression>),
+}
+
+/// Why a tag expression could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagExpressionError {
+  pub expression: String,
+  pub message: String,
+}
+
+impl fmt::Display for TagExpressionError {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "Invalid tag expression \"{}\": {}", self.expression, self.message)
+  }
+}
+
+impl Error for TagExpressionError {
+  fn description(&self) -> &str {
+    &self.message
+  }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+  Open,
+  Close,
+  And,
+  Or,
+  Not,
+  Tag(String),
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, String> {
+  let mut tokens = Vec::new();
+  let mut word = String::new();
+  let mut chars = expression.chars();
+
+  // Words end at whitespace and parentheses
+  fn end_word(word: &mut String, tokens: &mut Vec<Token>) -> Result<(), String> {
+    if word.is_empty() {
+      return Ok(());
+    }
+    let token = match word.as_str() {
+      "and" => Token::And,
+      "or" => Token::Or,
+      "not" => Token::Not,
+      tag if tag.starts_with('@') => Token::Tag(tag.to_owned()),
+      other => return Err(format!("Tags must start with @, found \"{}\"", other)),
+    };
+    tokens.push(token);
+    word.clear();
+    Ok(())
+  }
+
+  while let Some(c) = chars.next() {
+    match c {
+      '\\' => {
+        match chars.next() {
+          Some(escaped) => word.push(escaped),
+          None => return Err("Expected a character after \\".to_owned()),
+        }
+      },
+      '(' | ')' => {
+        try!(end_word(&mut word, &mut tokens));
+        tokens.push(if c == '(' { Token::Open } else { Token::Close });
+      },
+      c if c.is_whitespace() => try!(end_word(&mut word, &mut tokens)),
+      c => word.push(c),
+    }
+  }
+  try!(end_word(&mut word, &mut tokens));
+
+  Ok(tokens)
+}
+
+struct Parser {
+  tokens: Vec<Token>,
+  position: usize,
+}
+
+impl Parser {
+  fn peek(&self) -> Option<&Token> {
+    self.tokens.get(self.position)
+  }
+
+  fn next(&mut self) -> Option<Token> {
+    self.position += 1;
+    self.tokens.get(self.position - 1).cloned()
+  }
+
+  fn or(&mut self) -> Result<TagExpression, String> {
+    let mut expression = try!(self.and());
+    while self.peek() == Some(&Token::Or) {
+      self.next();
+      expression = TagExpression::Or(Box::new(expression), Box::new(try!(self.and())));
+    }
+    Ok(expression)
+  }
+
+  fn and(&mut self) -> Result<TagExpression, String> {
+    let mut expression = try!(self.not());
+    while self.peek() == Some(&Token::And) {
+      self.next();
+      expression = TagExpression::And(Box::new(expression), Box::new(try!(self.not())));
+    }
+    Ok(expression)
+  }
+
+  fn not(&mut self) -> Result<TagExpression, String> {
+    match self.next() {
+      Some(Token::Not) => Ok(TagExpression::Not(Box::new(try!(self.not())))),
+      Some(Token::Tag(tag)) => Ok(TagExpression::Tag(tag)),
+      Some(Token::Open) => {
+        let expression = try!(self.or());
+        match self.next() {
+          Some(Token::Close) => Ok(expression),
+          _ => Err("Unclosed (".to_owned()),
+        }
+      },
+      Some(Token::Close) => Err("Unexpected )".to_owned()),
+      Some(_) => Err("Expected a tag before and/or".to_owned()),
+      None => Err("Expected a tag at the end".to_owned()),
+    }
+  }
+}
+
+impl TagExpression {
+  pub fn parse(expression: &str) -> Result<TagExpression, TagExpressionError> {
+    let error = |message| {
+      TagExpressionError {
+        expression: expression.to_owned(),
+        message: message,
+      }
+    };
+
+    let tokens = try!(tokenize(expression).map_err(&error));
+    if tokens.is_empty() {
+      return Err(error("Empty tag expression".to_owned()));
+    }
+
+    let mut parser = Parser {
+      tokens: tokens,
+      position: 0,
+    };
+    let parsed = try!(parser.or().map_err(&error));
+    match parser.peek() {
+      None => Ok(parsed),
+      Some(&Token::Close) => Err(error("Unexpected )".to_owned())),
+      Some(_) => Err(error("Expected and/or between tags".to_owned())),
+    }
+  }
+
+  /// Whether a scenario with the given tags is selected by the expression
+  pub fn matches(&self, tags: &[String]) -> bool {
+    match self {
+      &TagExpression::Tag(ref tag) => tags.iter().any(|t| t == tag),
+      &TagExpression::Not(ref expression) => !expression.matches(tags),
+      &TagExpression::And(ref left, ref right) => left.matches(tags) && right.matches(tags),
+      &TagExpression::Or(ref left, ref right) => left.matches(tags) || right.matches(tags),
+    }
+  }
+}
+
+impl FromStr for TagExpression {
+  type Err = TagExpressionError;
+
+  fn from_str(expression: &str) -> Result<TagExpression, TagExpressionError> {
+    TagExpression::parse(expression)
+  }
+}
+
+// `or` binds looser than `and`, so needs parentheses within it
+fn and_operand(expression: &TagExpression) -> String {
+  match expression {
+    &TagExpression::Or(..) => format!("({})", expression),
+    _ => expression.to_string(),
+  }
+}
+
+impl fmt::Display for TagExpression {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      &TagExpression::Tag(ref tag) => {
+        for c in tag.chars() {
+          if c == '\\' || c == '(' || c == ')' || c.is_whitespace() {
+            try!(write!(f, "\\"));
+          }
+          try!(write!(f, "{}", c));
+        }
+        Ok(())
+      },
+      &TagExpression::Not(ref expression) => {
+        match **expression {
+          TagExpression::And(..) | TagExpression::Or(..) => write!(f, "not ({})", expression),
+          _ => write!(f, "not {}", expression),
+        }
+      },
+      &TagExpression::And(ref left, ref right) => {
+        write!(f, "{} and {}", and_operand(left), and_operand(right))
+      },
+      &TagExpression::Or(ref left, ref right) => {
+        match **right {
+          TagExpression::Or(..) => write!(f, "{} or ({})", left, right),
+          _ => write!(f, "{} or {}", left, right),
+        }
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use gherkin;
+
+  fn tags(tags: &[&str]) -> Vec<String> {
+    tags.iter().map(|tag| tag.to_string()).collect()
+  }
+
+  fn tag(name: &str) -> Box<TagExpression> {
+    Box::new(TagExpression::Tag(name.to_owned()))
+  }
+
+  #[test]
+  fn it_parses_with_precedence() {
+    let not_c = Box::new(TagExpression::Not(tag("@c")));
+    assert_eq!(TagExpression::parse("@a or @b and not @c").unwrap(),
+               TagExpression::Or(tag("@a"), Box::new(TagExpression::And(tag("@b"), not_c))));
+    assert_eq!(TagExpression::parse("(@a or @b) and @c").unwrap(),
+               TagExpression::And(Box::new(TagExpression::Or(tag("@a"), tag("@b"))), tag("@c")));
+    assert_eq!(TagExpression::parse("@with\\ space").unwrap(),
+               TagExpression::Tag("@with space".to_owned()));
+  }
+
+  #[test]
+  fn it_matches_tags() {
+    let expression = TagExpression::parse("@smoke and not (@slow or @wip)").unwrap();
+    assert!(expression.matches(&tags(&["@smoke"])));
+    assert!(expression.matches(&tags(&["@smoke", "@fast"])));
+    assert!(!expression.matches(&tags(&["@smoke", "@slow"])));
+    assert!(!expression.matches(&tags(&["@wip"])));
+    assert!(!expression.matches(&tags(&[])));
+  }
+
+  #[test]
+  fn it_rejects_invalid_expressions() {
+    let message = |expression| TagExpression::parse(expression).unwrap_err().message;
+    assert_eq!(message(""), "Empty tag expression");
+    assert_eq!(message("@a and"), "Expected a tag at the end");
+    assert_eq!(message("@a @b"), "Expected and/or between tags");
+    assert_eq!(message("(@a or @b"), "Unclosed (");
+    assert_eq!(message("@a)"), "Unexpected )");
+    assert_eq!(message("smoke"), "Tags must start with @, found \"smoke\"");
+    assert_eq!(TagExpression::parse("@a or").unwrap_err().to_string(),
+               "Invalid tag expression \"@a or\": Expected a tag at the end");
+  }
+
+  #[test]
+  fn it_inherits_tags() {
+    let feature = gherkin::parse("test.feature",
+                                 "@billing\nFeature: F\n  @rules\n  Rule: R\n    @outline\n    \
+                                  Scenario Outline: O\n      Given <x>\n\n      @row\n      \
+                                  Examples:\n        | x |\n        | a |\n")
+      .unwrap();
+    let inherited = Tags::of_scenario(&feature, &feature.scenarios[0]);
+
+    assert_eq!(inherited.names(), tags(&["@billing", "@rules", "@outline", "@row"]));
+    assert_eq!(inherited.at(TagLevel::Rule), vec!["@rules"]);
+    assert_eq!(inherited.at(TagLevel::Examples), vec!["@row"]);
+    assert!(inherited.contains("@outline"));
+    assert!(inherited.contains("billing"));
+    assert!(!inherited.contains("@other"));
+    assert!(inherited.matches(&TagExpression::parse("@billing and @row").unwrap()));
+  }
+
+  #[test]
+  fn it_adds_missing_ats_to_wire_tags() {
+    let wire = Tags::from_wire(tags(&["db", "@slow"]));
+    assert_eq!(wire.names(), tags(&["@db", "@slow"]));
+    assert_eq!(wire.at(TagLevel::Scenario), vec!["@db", "@slow"]);
+    assert!(Tags::new().is_empty());
+  }
+
+  #[test]
+  fn it_displays_expressions() {
+    for expression in &["@a or @b and not @c",
+                        "(@a or @b) and @c",
+                        "not (@a and @b)",
+                        "@a or (@b or @c)",
+                        "@with\\ space"] {
+      assert_eq!(TagExpression::parse(expression).unwrap().to_string(), *expression);
+    }
+  }
+}
@@ -0,0 +1,282 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use definitions::listing::StepDefinition;
+use state::StepId;
+
+/// How many of the slowest invocations are kept for each step definition
+pub const SLOWEST_KEPT: usize = 5;
+
+/// A single invocation of a step definition
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invocation {
+  /// The text of the step that was invoked, without its keyword
+  pub step: String,
+  pub duration: Duration,
+}
+
+/// How often, and how long, a single step definition was used
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct StepStats {
+  /// The number of step matches the definition appeared in
+  pub matched: usize,
+  /// The number of times the definition was invoked
+  pub invoked: usize,
+  pub total_time: Duration,
+  pub max_time: Duration,
+  /// The slowest invocations, slowest first, up to
+  /// [SLOWEST_KEPT](./constant.SLOWEST_KEPT.html) of them
+  pub slowest: Vec<Invocation>,
+}
+
+impl StepStats {
+  /// The mean time taken by an invocation, if there were any
+  pub fn mean_time(&self) -> Option<Duration> {
+    if self.invoked == 0 {
+      None
+    } else {
+      Some(self.total_time / self.invoked as u32)
+    }
+  }
+}
+
+/// How often a single step definition was used, now with its timings too
+#[deprecated(note = "Renamed to StepStats")]
+pub type UsageCounts = StepStats;
+
+#[derive(Debug, Default)]
+struct UsageState {
+  stats: HashMap<StepId, StepStats>,
+  // Invokes only carry a step id, so the text of the step is taken from the
+  // most recent match that offered the id
+  last_matched: HashMap<StepId, String>,
+}
+
+/// Usage of step definitions across a run
+///
+/// A [WorldRunner](../runner/struct.WorldRunner.html) records every match and
+/// invoke of its steps here. The usage is shared between clones, so a clone
+/// taken before the runner is handed to a
+/// [Server](../server/struct.Server.html) can be inspected once the run is
+/// over.
+///
+/// # Example
+///
+/// ```no_run
+/// use cucumber::{Server, WorldRunner};
+///
+/// fn main() {
+///   let world: u32 = 0;
+///   let runner = WorldRunner::new(world);
+///   let usage = runner.usage();
+///   let definitions: Vec<_> = runner.cucumber().definitions().collect();
+///   let (handle, stop_tx) = Server::new(runner).start(None);
+///
+///   /*
+///    * Execute remote gherkin parser
+///    */
+///
+///   stop_tx.send(()).unwrap();
+///   handle.join().unwrap();
+///
+///   for definition in usage.unused(definitions) {
+///     println!("Unused step {} at {}", definition.pattern, definition.source);
+///   }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StepUsage {
+  state: Arc<Mutex<UsageState>>,
+}
+
+impl StepUsage {
+  pub fn new() -> StepUsage {
+    StepUsage::default()
+  }
+
+  /// Record that a step definition was offered as a match for a step
+  pub fn record_match(&self, id: StepId, step: &str) {
+    let mut state = self.state.lock().unwrap();
+    state.stats.entry(id).or_insert_with(StepStats::default).matched += 1;
+    state.last_matched.insert(id, step.to_owned());
+  }
+
+  /// Record that a step definition was invoked, and how long it took
+  pub fn record_invoke(&self, id: StepId, duration: Duration) {
+    let mut state = self.state.lock().unwrap();
+    let step = state.last_matched.get(&id).cloned().unwrap_or_else(String::new);
+    let stats = state.stats.entry(id).or_insert_with(StepStats::default);
+
+    stats.invoked += 1;
+    stats.total_time += duration;
+    if duration > stats.max_time {
+      stats.max_time = duration;
+    }
+
+    let idx = stats.slowest.iter().position(|slow| duration > slow.duration);
+    let idx = idx.unwrap_or(stats.slowest.len());
+    if idx < SLOWEST_KEPT {
+      stats.slowest.insert(idx,
+                           Invocation {
+                             step: step,
+                             duration: duration,
+                           });
+      stats.slowest.truncate(SLOWEST_KEPT);
+    }
+  }
+
+  /// How often and how long a step definition has been used so far
+  pub fn stats(&self, id: StepId) -> StepStats {
+    self.state.lock().unwrap().stats.get(&id).cloned().unwrap_or(StepStats::default())
+  }
+
+  /// How often a step definition has been used so far
+  #[deprecated(note = "Renamed to stats")]
+  pub fn counts(&self, id: StepId) -> StepStats {
+    self.stats(id)
+  }
+
+  /// Filter the step definitions that were never matched or invoked
+  pub fn unused<I>(&self, definitions: I) -> Vec<StepDefinition>
+    where I: IntoIterator<Item = StepDefinition>
+  {
+    let state = self.state.lock().unwrap();
+    definitions.into_iter()
+      .filter(|def| {
+        state.stats.get(&def.id).map(|s| s.matched == 0 && s.invoked == 0).unwrap_or(true)
+      })
+      .collect()
+  }
+
+  /// Render a report of the usage of each step definition, in the manner of
+  /// Cucumber's `usage` formatter
+  ///
+  /// Definitions are listed slowest first by mean invocation time, each
+  /// followed by its slowest invocations. Unused definitions come last.
+  pub fn report<I>(&self, definitions: I) -> String
+    where I: IntoIterator<Item = StepDefinition>
+  {
+    let mut definitions: Vec<(StepDefinition, StepStats)> =
+      definitions.into_iter().map(|def| (def.clone(), self.stats(def.id))).collect();
+    definitions.sort_by(|&(_, ref a), &(_, ref b)| {
+      match (a.mean_time(), b.mean_time()) {
+        (Some(a), Some(b)) => b.cmp(&a),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => b.matched.cmp(&a.matched),
+      }
+    });
+
+    definitions.iter()
+      .map(|&(ref def, ref stats)| {
+        let summary = match stats.mean_time() {
+          Some(mean) => {
+            format!("{} matched, {} invoked, total {}, mean {}, max {}",
+                    stats.matched,
+                    stats.invoked,
+                    format_duration(stats.total_time),
+                    format_duration(mean),
+                    format_duration(stats.max_time))
+          },
+          None if stats.matched > 0 => format!("{} matched, never invoked", stats.matched),
+          None => "NOT MATCHED BY ANY STEPS".to_owned(),
+        };
+        let slowest = stats.slowest
+          .iter()
+          .map(|slow| format!("  {} # {}\n", slow.step, format_duration(slow.duration)))
+          .collect::<Vec<_>>()
+          .concat();
+
+        format!("/{}/ # {}\n  {}\n{}", def.pattern, def.source, summary, slowest)
+      })
+      .collect::<Vec<_>>()
+      .concat()
+  }
+}
+
+fn format_duration(duration: Duration) -> String {
+  format!("{}.{:03}s", duration.as_secs(), duration.subsec_nanos() / 1000000)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  use std::time::Duration;
+
+  use definitions::listing::StepDefinition;
+
+  fn definition(id: u32) -> StepDefinition {
+    StepDefinition {
+      id: id,
+      keyword: None,
+      pattern: format!("^step {}$", id),
+      source: format!("file:{}", id),
+      arity: 0,
+    }
+  }
+
+  #[test]
+  fn it_counts_matches_and_invokes() {
+    let usage = StepUsage::new();
+    let shared = usage.clone();
+    shared.record_match(1, "step 1");
+    shared.record_match(1, "step 1");
+    shared.record_invoke(1, Duration::from_millis(30));
+    shared.record_invoke(1, Duration::from_millis(10));
+
+    let stats = usage.stats(1);
+    assert_eq!(stats.matched, 2);
+    assert_eq!(stats.invoked, 2);
+    assert_eq!(stats.total_time, Duration::from_millis(40));
+    assert_eq!(stats.max_time, Duration::from_millis(30));
+    assert_eq!(stats.mean_time(), Some(Duration::from_millis(20)));
+    assert_eq!(usage.stats(2), StepStats::default());
+  }
+
+  #[test]
+  fn it_keeps_the_slowest_invocations() {
+    let usage = StepUsage::new();
+    for millis in 1..(SLOWEST_KEPT as u64 + 3) {
+      usage.record_match(0, &format!("step {}", millis));
+      usage.record_invoke(0, Duration::from_millis(millis));
+    }
+
+    let slowest = usage.stats(0).slowest;
+    assert_eq!(slowest.len(), SLOWEST_KEPT);
+    assert_eq!(slowest[0],
+               Invocation {
+                 step: format!("step {}", SLOWEST_KEPT + 2),
+                 duration: Duration::from_millis(SLOWEST_KEPT as u64 + 2),
+               });
+    assert!(slowest.windows(2).all(|pair| pair[0].duration >= pair[1].duration));
+  }
+
+  #[test]
+  fn it_finds_unused_definitions() {
+    let usage = StepUsage::new();
+    usage.record_match(0, "step 0");
+    usage.record_invoke(2, Duration::from_millis(1));
+
+    assert_eq!(usage.unused(vec![definition(0), definition(1), definition(2), definition(3)]),
+               vec![definition(1), definition(3)]);
+  }
+
+  #[test]
+  fn it_reports_usage_slowest_first() {
+    let usage = StepUsage::new();
+    usage.record_match(0, "step 0");
+    usage.record_invoke(0, Duration::from_millis(5));
+    usage.record_match(1, "step 1");
+    usage.record_invoke(1, Duration::from_millis(1500));
+    usage.record_match(2, "step 2");
+
+    assert_eq!(usage.report(vec![definition(0), definition(1), definition(2), definition(3)]),
+               "/^step 1$/ # file:1\n  1 matched, 1 invoked, total 1.500s, mean 1.500s, max \
+                1.500s\n  step 1 # 1.500s\n/^step 0$/ # file:0\n  1 matched, 1 invoked, total \
+                0.005s, mean 0.005s, max 0.005s\n  step 0 # 0.005s\n/^step 2$/ # file:2\n  1 \
+                matched, never invoked\n/^step 3$/ # file:3\n  NOT MATCHED BY ANY STEPS\n");
+  }
+}